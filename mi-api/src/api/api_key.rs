@@ -0,0 +1,102 @@
+use axum::debug_handler;
+use axum::extract::{Path, State};
+use chrono::{Duration, Utc};
+use mi_db::{ApiKey, ApiKeyScope};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::result::{AppResult, Json};
+use crate::state::{hash_api_key, SharedState};
+use crate::SessionAuthUserId;
+
+/// Prefix of every personal API key. Lets a key be told apart from other tokens at a glance.
+pub const API_KEY_PREFIX: &str = "mi_";
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateApiKeyRequest {
+    /// Name of the key, to tell the keys apart
+    #[validate(length(min = 1, max = 64))]
+    name: String,
+    #[validate(length(min = 1))]
+    scopes: Vec<ApiKeyScope>,
+    /// Days until the key expires. The key does not expire if missing
+    #[schema(minimum = 1, maximum = 365)]
+    #[validate(range(min = 1, max = 365))]
+    expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    /// The key to send as `Authorization: Bearer`. It is not shown again
+    key: String,
+    api_key: ApiKey,
+}
+
+#[utoipa::path(
+    post,
+    path = "/key/create",
+    request_body = CreateApiKeyRequest,
+    responses((status = 200, description = "API key is created", body = CreateApiKeyResponse)),
+    security(("session_cookie" = [])),
+)]
+#[debug_handler]
+pub async fn create_api_key(
+    SessionAuthUserId(user_id): SessionAuthUserId,
+    State(state): State<SharedState>,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> AppResult<Json<CreateApiKeyResponse>> {
+    request.validate()?;
+
+    let key = state.generate_api_key();
+    let expires_at = request
+        .expires_in_days
+        .map(|days| Utc::now() + Duration::days(days));
+
+    let api_key = state
+        .postgres()
+        .insert_api_key(
+            user_id,
+            &request.name,
+            &hash_api_key(&key),
+            &request.scopes,
+            expires_at,
+        )
+        .await?;
+
+    Ok(Json(CreateApiKeyResponse { key, api_key }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/key/list",
+    responses((status = 200, description = "API keys of the session owner, newest first", body = [ApiKey])),
+    security(("session_cookie" = [])),
+)]
+#[debug_handler]
+pub async fn get_api_keys(
+    SessionAuthUserId(user_id): SessionAuthUserId,
+    State(state): State<SharedState>,
+) -> AppResult<Json<Vec<ApiKey>>> {
+    let api_keys = state.postgres().get_user_api_keys(user_id).await?;
+
+    Ok(Json(api_keys))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/key/revoke/{key_id}",
+    responses((status = 200, description = "API key is revoked")),
+    params(("key_id", description = "Id of the API key to revoke")),
+    security(("session_cookie" = [])),
+)]
+#[debug_handler]
+pub async fn revoke_api_key(
+    SessionAuthUserId(user_id): SessionAuthUserId,
+    State(state): State<SharedState>,
+    Path(key_id): Path<i64>,
+) -> AppResult<()> {
+    state.postgres().delete_api_key(user_id, key_id).await?;
+
+    Ok(())
+}
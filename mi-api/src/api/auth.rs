@@ -1,16 +1,21 @@
 use axum::debug_handler;
 use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap};
 use axum::response::Redirect;
+use mi_db::auth::AuthError;
 use mi_db::user::UserError;
 use mi_osu_api::OsuApiError;
 use once_cell::sync::Lazy;
 use serde::Deserialize;
+use tower_cookies::cookie::time::Duration;
+use tower_cookies::cookie::SameSite;
 use tower_cookies::{Cookie, Cookies};
-use tracing::info;
+use tracing::{info, warn};
 
+use crate::api::session::new_session;
 use crate::result::{AppError, AppResult};
 use crate::state::SharedState;
-use crate::{get_session_cookie, SessionError, COOKIE_NAME};
+use crate::{get_session_cookie, session_cookie, SessionError};
 
 static REDIRECT_URI: Lazy<String> = Lazy::new(|| {
     std::env::var("MI_AUTH_REDIRECT_URI")
@@ -25,17 +30,103 @@ static OSU_REDIRECT_URI: Lazy<String> = Lazy::new(|| {
     std::env::var("OSU_REDIRECT_URI").expect("Environment variable OSU_REDIRECT_URI is not set.")
 });
 
+/// Paths users can be sent back to after logging in, read from the comma separated
+/// `MI_AUTH_RETURN_TO_ALLOWLIST`. A path is allowed if it is one of these or under one of them.
+static RETURN_TO_ALLOWLIST: Lazy<Vec<String>> = Lazy::new(|| {
+    std::env::var("MI_AUTH_RETURN_TO_ALLOWLIST")
+        .unwrap_or_else(|_| DEFAULT_RETURN_TO_ALLOWLIST.to_string())
+        .split(',')
+        .map(|path| path.trim().trim_end_matches('/').to_string())
+        .filter(|path| !path.is_empty())
+        .collect()
+});
+
+const DEFAULT_RETURN_TO_ALLOWLIST: &str = "/profile,/dashboard";
+const OAUTH_STATE_COOKIE_NAME: &str = "mi-oauth-state";
+/// Same as the lifetime of the state on Redis.
+const OAUTH_STATE_COOKIE_MAX_AGE_MINUTES: i64 = 10;
+
+/// Whether the user can be sent to `return_to` after logging in.
+///
+/// Only paths on our own frontend that are under [`RETURN_TO_ALLOWLIST`] are allowed, so the
+/// login can't be used to redirect users to other sites.
+fn is_allowed_return_to(return_to: &str) -> bool {
+    let is_local_path = return_to.starts_with('/')
+        && !return_to.starts_with("//")
+        && return_to.chars().all(|c| c.is_ascii_graphic() && c != '\\');
+    if !is_local_path {
+        return false;
+    }
+
+    // Percent-encoded paths are rejected as a whole since encoded dots and slashes can turn into
+    // a traversal once the browser or a proxy decodes them, even if they were encoded twice
+    let path = return_to.split(['?', '#']).next().unwrap_or_default();
+    if path.contains('%') || path.split('/').any(|segment| segment == "..") {
+        return false;
+    }
+
+    RETURN_TO_ALLOWLIST.iter().any(|allowed| {
+        path.strip_prefix(allowed.as_str())
+            .map_or(false, |rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+/// Url the user is sent to after logging in.
+fn post_login_redirect_uri(return_to: Option<&str>) -> String {
+    match return_to {
+        Some(return_to) => format!("{}{}", REDIRECT_URI.trim_end_matches('/'), return_to),
+        None => (*REDIRECT_URI).to_string(),
+    }
+}
+
+/// Checks the `state` osu! sent back against the one stored when the login started, and returns
+/// the path to return to after the login.
+///
+/// The state has to match the cookie of the browser that started the login, which stops
+/// attackers from logging users in to their own account with a callback url of their own.
+async fn verify_oauth_state(
+    state: &SharedState,
+    cookies: &Cookies,
+    oauth_state: Option<&str>,
+) -> AppResult<Option<String>> {
+    let cookie_state = cookies
+        .get(OAUTH_STATE_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_string());
+    cookies.remove(
+        Cookie::build(OAUTH_STATE_COOKIE_NAME, "")
+            .path("/")
+            .finish(),
+    );
+
+    let (Some(oauth_state), Some(cookie_state)) = (oauth_state, cookie_state) else {
+        return Err(SessionError::InvalidOAuthState.into());
+    };
+    if oauth_state != cookie_state {
+        return Err(SessionError::InvalidOAuthState.into());
+    }
+
+    match state.redis().take_oauth_state(oauth_state).await {
+        Ok(return_to) => Ok(return_to),
+        Err(AuthError::ValueNotFound { .. }) => Err(SessionError::InvalidOAuthState.into()),
+        Err(err) => Err(err.into()),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct OsuAuthResponseParams {
     code: Option<String>,
     error: Option<String>,
+    state: Option<String>,
 }
 
 pub async fn authorize_from_osu_api(
     Query(params): Query<OsuAuthResponseParams>,
+    headers: HeaderMap,
     cookies: Cookies,
     State(state): State<SharedState>,
 ) -> Result<Redirect, AppError> {
+    let return_to = verify_oauth_state(&state, &cookies, params.state.as_deref()).await?;
+
     if let Some(err) = params.error {
         // TODO: Better error handling
         return Err(SessionError::OsuAuthError(err).into());
@@ -63,17 +154,25 @@ pub async fn authorize_from_osu_api(
         .await?;
 
     let session_token = state.generate_session_token();
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|user_agent| user_agent.to_str().ok());
+    let session = new_session(&state, user_agent);
+    let session_hash = state.hash_session_token(session_token);
 
     tokio::try_join!(
-        state.redis().set_session_token(user.id, session_token),
+        state
+            .redis()
+            .set_session_token(user.id, &session_hash, &session),
         state.redis().set_osu_tokens(
             user.id,
             &auth_response.access_token,
             &auth_response.refresh_token,
+            auth_response.expires_in as usize,
         )
     )?;
 
-    cookies.add(Cookie::new(COOKIE_NAME, session_token.to_string()));
+    cookies.add(session_cookie(session_token));
 
     match state.postgres().get_user(user.id).await {
         Ok(_) => {}
@@ -86,7 +185,7 @@ pub async fn authorize_from_osu_api(
         }
     }
 
-    let redirect_uri = (*REDIRECT_URI).to_string();
+    let redirect_uri = post_login_redirect_uri(return_to.as_deref());
 
     Ok(Redirect::to(&redirect_uri))
 }
@@ -96,7 +195,10 @@ pub async fn cookie_page(cookies: Cookies, State(state): State<SharedState>) ->
     let cookie = get_session_cookie(&cookies);
     match cookie {
         Ok(session_token) => {
-            let user_id = state.redis().get_user_id(session_token).await?;
+            let user_id = state
+                .redis()
+                .get_user_id(&state.hash_session_token(session_token))
+                .await?;
             let user = state.postgres().get_user(user_id).await?;
             Ok(format!(
                 "This is the cookie page and your cookie is: {}\nYour user info: {:#?}",
@@ -107,23 +209,113 @@ pub async fn cookie_page(cookies: Cookies, State(state): State<SharedState>) ->
     }
 }
 
-pub async fn login(cookies: Cookies, State(state): State<SharedState>) -> AppResult<Redirect> {
+#[derive(Debug, Deserialize)]
+pub struct LoginParams {
+    /// Path on the frontend to send the user to after logging in
+    return_to: Option<String>,
+}
+
+pub async fn login(
+    Query(params): Query<LoginParams>,
+    cookies: Cookies,
+    State(state): State<SharedState>,
+) -> AppResult<Redirect> {
+    let return_to = params.return_to.filter(|return_to| {
+        let is_allowed = is_allowed_return_to(return_to);
+        if !is_allowed {
+            warn!(return_to, "Ignoring return path that is not allowed");
+        }
+        is_allowed
+    });
+
     let cookie = get_session_cookie(&cookies);
 
     if let Ok(session_token) = cookie {
-        let user_id_res = state.redis().get_user_id(session_token).await;
+        let user_id_res = state
+            .redis()
+            .get_user_id(&state.hash_session_token(session_token))
+            .await;
 
         // User already authed and their session tokens are on the redis DB
         if user_id_res.is_ok() {
-            return Ok(Redirect::to(&REDIRECT_URI));
+            return Ok(Redirect::to(&post_login_redirect_uri(return_to.as_deref())));
         }
     }
 
+    let oauth_state = state.generate_oauth_state();
+    state
+        .redis()
+        .set_oauth_state(&oauth_state, return_to.as_deref())
+        .await?;
+    // Lax, so the cookie is sent along when osu! redirects the user back to us.
+    cookies.add(
+        Cookie::build(OAUTH_STATE_COOKIE_NAME, oauth_state.clone())
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .max_age(Duration::minutes(OAUTH_STATE_COOKIE_MAX_AGE_MINUTES))
+            .finish(),
+    );
+
     let redirect_uri = format!(
-        "https://osu.ppy.sh/oauth/authorize?response_type=code&client_id={}&redirect_uri={}&scope=public+identify",
-        *OSU_CLIENT_ID, *OSU_REDIRECT_URI
+        "https://osu.ppy.sh/oauth/authorize?response_type=code&client_id={}&redirect_uri={}&scope=public+identify&state={}",
+        *OSU_CLIENT_ID, *OSU_REDIRECT_URI, oauth_state
     );
 
     info!(redirect_uri, "Redirecting");
     Ok(Redirect::to(&redirect_uri))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_return_to() {
+        for return_to in [
+            "/profile",
+            "/profile/",
+            "/profile/123",
+            "/dashboard",
+            "/dashboard/settings",
+            "/profile/123?tab=influences",
+            "/profile/123#mentions",
+            "/profile?next=%2F%2Fevil.com",
+        ] {
+            assert!(
+                is_allowed_return_to(return_to),
+                "{return_to} should be allowed"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejected_return_to() {
+        for return_to in [
+            "",
+            "profile",
+            "https://evil.com/profile",
+            "//evil.com",
+            "//evil.com/profile",
+            "/\\evil.com",
+            "/profile\\..\\admin",
+            "/profile/../admin",
+            "/profile/..",
+            "/profile/%2e%2e/admin",
+            "/profile/%2E%2E/admin",
+            "/profile/%2e%2e%2fadmin",
+            "/profile%2f..%2fadmin",
+            "/profile/%252e%252e/admin",
+            "/profiles",
+            "/profilex/123",
+            "/admin?return=/profile",
+            "/admin#/profile",
+            "/profile/1 2",
+        ] {
+            assert!(
+                !is_allowed_return_to(return_to),
+                "{return_to} should be rejected"
+            );
+        }
+    }
+}
@@ -1,29 +1,240 @@
 use axum::debug_handler;
-use axum::extract::{Path, State};
-use mi_db::influence::Influence;
+use axum::extract::{Path, Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use mi_core::{AppErrorExt, ErrorType};
+use mi_db::influence::{
+    Influence, InfluenceAcknowledgement, InfluenceDirection, InfluenceError, InfluenceListOptions,
+    InfluenceReorderEntry, InfluenceSort, SortOrder,
+};
+use mi_db::influence_beatmap::{InfluenceBeatmap, INFLUENCE_BEATMAP_LIMIT};
+use mi_db::influence_graph::{InfluenceChain, InfluenceGraph};
+use mi_db::influence_history::{InfluenceAction, InfluenceHistory};
+use mi_db::influence_tag::InfluenceTag;
+use mi_osu_api::OsuApiError;
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use thiserror::Error;
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
-use crate::result::{AppResult, Json};
+use crate::result::{AppError, AppResult, Json};
 use crate::state::SharedState;
-use crate::AuthUserId;
+use crate::{AuthUserId, MaybeAuthUserId};
+
+#[derive(Debug, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetInfluencesQuery {
+    /// Whether to list the users that influenced the user or the users they influenced.
+    /// Defaults to `influencers`
+    #[serde(default)]
+    direction: InfluenceDirection,
+    /// Column to sort the influences with. Defaults to `created_at`
+    #[serde(default)]
+    sort: InfluenceSort,
+    /// Sort order. Defaults to `desc`
+    #[serde(default)]
+    order: SortOrder,
+    /// Only list the influences with this tag
+    tag: Option<String>,
+    /// Maximum number of influences to return. Defaults to 50
+    #[param(minimum = 1, maximum = 100)]
+    #[validate(range(min = 1, max = 100))]
+    limit: Option<i64>,
+    /// Number of influences to skip. Defaults to 0
+    #[param(minimum = 0)]
+    #[validate(range(min = 0))]
+    offset: Option<i64>,
+}
+
+impl From<GetInfluencesQuery> for InfluenceListOptions {
+    fn from(query: GetInfluencesQuery) -> Self {
+        let default = InfluenceListOptions::default();
+        Self {
+            direction: query.direction,
+            sort: query.sort,
+            order: query.order,
+            tag: query.tag,
+            viewer_id: None,
+            limit: query.limit.unwrap_or(default.limit),
+            offset: query.offset.unwrap_or(default.offset),
+        }
+    }
+}
 
 #[utoipa::path(
     get,
     path = "/influence/get/{user_id}/",
+    params(("user_id", description = "Osu! ID of the user"), GetInfluencesQuery),
     responses((status = 200, description = "List of influences", body = [Influence])),
+    security((), ("session_cookie" = []), ("api_key" = [])),
 )]
 #[debug_handler]
 pub async fn get_influences(
+    MaybeAuthUserId(viewer_id): MaybeAuthUserId,
     State(state): State<SharedState>,
     Path(query_user_id): Path<i64>,
+    Query(query): Query<GetInfluencesQuery>,
 ) -> AppResult<Json<Vec<Influence>>> {
-    let influences = state.postgres().get_user_influencers(query_user_id).await?;
+    query.validate()?;
+    state
+        .postgres()
+        .ensure_profile_visible(query_user_id, viewer_id)
+        .await?;
+
+    let mut options = InfluenceListOptions::from(query);
+    options.viewer_id = viewer_id;
+
+    let influences = state
+        .postgres()
+        .get_influences_page(query_user_id, options)
+        .await?;
 
     Ok(Json(influences))
 }
 
+/// Maximum number of edges returned from a single influence tree request.
+const INFLUENCE_TREE_MAX_EDGES: i64 = 500;
+
+#[derive(Debug, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetInfluenceTreeQuery {
+    /// `influencers` walks the ancestry of the user, `influenced` walks the descendants. Defaults
+    /// to `influencers`
+    #[serde(default)]
+    direction: InfluenceDirection,
+    /// Maximum number of hops from the user. Defaults to 3
+    #[param(minimum = 1, maximum = 6)]
+    #[validate(range(min = 1, max = 6))]
+    depth: Option<i32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/influence/tree/{user_id}/",
+    params(("user_id", description = "Osu! ID of the user"), GetInfluenceTreeQuery),
+    responses((status = 200, description = "Influence tree of the user", body = InfluenceGraph)),
+    security((), ("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn get_influence_tree(
+    MaybeAuthUserId(viewer_id): MaybeAuthUserId,
+    State(state): State<SharedState>,
+    Path(query_user_id): Path<i64>,
+    Query(query): Query<GetInfluenceTreeQuery>,
+) -> AppResult<Json<InfluenceGraph>> {
+    query.validate()?;
+    state
+        .postgres()
+        .ensure_profile_visible(query_user_id, viewer_id)
+        .await?;
+
+    let graph = state
+        .postgres()
+        .get_influence_graph(
+            query_user_id,
+            query.direction,
+            query.depth.unwrap_or(3),
+            INFLUENCE_TREE_MAX_EDGES,
+            viewer_id,
+        )
+        .await?;
+
+    Ok(Json(graph))
+}
+
+/// Maximum number of hops the strongest chain search walks. Every hop of it carries many partial
+/// chains, so it is kept lower than the shortest chain limit.
+const STRONGEST_CHAIN_MAX_DEPTH: i32 = 4;
+
+#[derive(Debug, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetInfluencePathQuery {
+    /// Maximum number of hops between the users. Defaults to 6
+    #[param(minimum = 1, maximum = 6)]
+    #[validate(range(min = 1, max = 6))]
+    max_depth: Option<i32>,
+    /// Also return the chain with the highest summed influence level. Defaults to false
+    #[serde(default)]
+    strongest: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InfluencePathResponse {
+    /// Chain with the fewest influences. Null if the users are not connected
+    shortest: Option<InfluenceChain>,
+    /// Chain with the highest summed influence level. Null if it is not requested or the users are
+    /// not connected within 4 hops
+    strongest: Option<InfluenceChain>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/influence/path/{from_id}/{to_id}/",
+    params(
+        ("from_id", description = "Osu! ID of the user at the start of the chain"),
+        ("to_id", description = "Osu! ID of the user at the end of the chain"),
+        GetInfluencePathQuery,
+    ),
+    responses((status = 200, description = "Influence chains between the users", body = InfluencePathResponse)),
+    security((), ("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn get_influence_path(
+    MaybeAuthUserId(viewer_id): MaybeAuthUserId,
+    State(state): State<SharedState>,
+    Path((from_id, to_id)): Path<(i64, i64)>,
+    Query(query): Query<GetInfluencePathQuery>,
+) -> AppResult<Json<InfluencePathResponse>> {
+    query.validate()?;
+    let max_depth = query.max_depth.unwrap_or(6);
+
+    tokio::try_join!(
+        state.postgres().ensure_profile_visible(from_id, viewer_id),
+        state.postgres().ensure_profile_visible(to_id, viewer_id),
+    )?;
+
+    let shortest = state
+        .postgres()
+        .get_shortest_influence_chain(from_id, to_id, max_depth, viewer_id)
+        .await?;
+
+    let strongest = match (&shortest, query.strongest) {
+        (Some(_), true) => {
+            state
+                .postgres()
+                .get_strongest_influence_chain(
+                    from_id,
+                    to_id,
+                    max_depth.min(STRONGEST_CHAIN_MAX_DEPTH),
+                    viewer_id,
+                )
+                .await?
+        }
+        _ => None,
+    };
+
+    Ok(Json(InfluencePathResponse {
+        shortest,
+        strongest,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/influence/tags/",
+    responses((status = 200, description = "Influence tag vocabulary", body = [InfluenceTag])),
+)]
+#[debug_handler]
+pub async fn get_influence_tags(
+    State(state): State<SharedState>,
+) -> AppResult<Json<Vec<InfluenceTag>>> {
+    let tags = state.postgres().get_influence_tags().await?;
+
+    Ok(Json(tags))
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct InsertInfluenceRequest {
     from_id: i64,
@@ -31,6 +242,9 @@ pub struct InsertInfluenceRequest {
     #[validate(range(min = 1, max = 9))]
     level: i32,
     info: Option<String>,
+    /// Names of the tags from the tag vocabulary
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
 #[utoipa::path(
@@ -38,6 +252,7 @@ pub struct InsertInfluenceRequest {
     path = "/influence/create/",
     request_body = InsertInfluenceRequest,
     responses((status = 200, description = "Influence successfully created")),
+    security(("session_cookie" = []), ("api_key" = [])),
 )]
 #[debug_handler]
 pub async fn create_influence(
@@ -47,7 +262,8 @@ pub async fn create_influence(
 ) -> AppResult<()> {
     request.validate()?;
 
-    let influence = Influence::new(request.from_id, user_id, request.level, request.info);
+    let influence = Influence::new(request.from_id, user_id, request.level, request.info)
+        .with_tags(request.tags);
     state.postgres().insert_influence(influence).await?;
 
     Ok(())
@@ -63,6 +279,7 @@ pub struct DeleteInfluenceRequest {
     path = "/influence/delete/{from_id}",
     request_body = DeleteInfluenceRequest,
     responses((status = 200, description = "Influence successfully deleted")),
+    security(("session_cookie" = []), ("api_key" = [])),
 )]
 #[debug_handler]
 pub async fn delete_influence(
@@ -70,11 +287,51 @@ pub async fn delete_influence(
     State(state): State<SharedState>,
     Path(from_id): Path<i64>,
 ) -> AppResult<()> {
-    state.postgres().delete_influence(from_id, user_id).await?;
+    state
+        .postgres()
+        .delete_influence(from_id, user_id, user_id)
+        .await?;
+
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/influence/restore/{from_id}",
+    params(("from_id", description = "Osu! ID of the influencer user")),
+    responses((status = 200, description = "Influence successfully restored")),
+    security(("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn restore_influence(
+    AuthUserId(user_id): AuthUserId,
+    State(state): State<SharedState>,
+    Path(from_id): Path<i64>,
+) -> AppResult<()> {
+    state
+        .postgres()
+        .restore_influence(from_id, user_id, user_id)
+        .await?;
 
     Ok(())
 }
 
+#[utoipa::path(
+    get,
+    path = "/influence/deleted/",
+    responses((status = 200, description = "Deleted influences of the session owner that can still be restored", body = [Influence])),
+    security(("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn get_deleted_influences(
+    AuthUserId(user_id): AuthUserId,
+    State(state): State<SharedState>,
+) -> AppResult<Json<Vec<Influence>>> {
+    let influences = state.postgres().get_deleted_influences(user_id).await?;
+
+    Ok(Json(influences))
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct UpdateInfluenceLevelRequest {
     from_id: i64,
@@ -88,6 +345,7 @@ pub struct UpdateInfluenceLevelRequest {
     path = "/influence/update/level/",
     request_body = UpdateInfluenceLevelRequest,
     responses((status = 200, description = "Influence level successfully updated")),
+    security(("session_cookie" = []), ("api_key" = [])),
 )]
 #[debug_handler]
 pub async fn update_influence_level(
@@ -99,7 +357,7 @@ pub async fn update_influence_level(
 
     state
         .postgres()
-        .update_influence_level(request.from_id, user_id, request.level)
+        .update_influence_level(request.from_id, user_id, request.level, user_id)
         .await?;
 
     Ok(())
@@ -110,6 +368,8 @@ pub struct UpdateInfluenceInfoRequest {
     from_id: i64,
     #[schema(nullable)]
     info: Option<String>,
+    /// Replaces the tags of the influence if present. Tags are left as they are if missing
+    tags: Option<Vec<String>>,
 }
 
 #[utoipa::path(
@@ -117,6 +377,7 @@ pub struct UpdateInfluenceInfoRequest {
     path = "/influence/update/info/",
     request_body = UpdateInfluenceInfoRequest,
     responses((status = 200, description = "Influence info successfully updated")),
+    security(("session_cookie" = []), ("api_key" = [])),
 )]
 #[debug_handler]
 pub async fn update_influence_info(
@@ -126,8 +387,757 @@ pub async fn update_influence_info(
 ) -> AppResult<()> {
     state
         .postgres()
-        .update_influence_info(request.from_id, user_id, request.info.as_deref())
+        .update_influence_info(
+            request.from_id,
+            user_id,
+            request.info.as_deref(),
+            request.tags.as_deref(),
+            user_id,
+        )
         .await?;
 
     Ok(())
 }
+
+#[derive(Debug, Error)]
+pub enum InfluenceBeatmapError {
+    #[error("No beatmap with id `{0}` exists on osu!")]
+    BeatmapNotFound(i64),
+    #[error("No beatmapset with id `{0}` exists on osu!")]
+    BeatmapsetNotFound(i64),
+}
+
+impl AppErrorExt for InfluenceBeatmapError {
+    fn user_message(&self) -> String {
+        self.to_string()
+    }
+
+    fn error_type(&self) -> ErrorType {
+        match self {
+            InfluenceBeatmapError::BeatmapNotFound(_) => ErrorType::BadRequestData,
+            InfluenceBeatmapError::BeatmapsetNotFound(_) => ErrorType::BadRequestData,
+        }
+    }
+
+    fn log_error(&self) {
+        warn!("{}", self)
+    }
+}
+
+/// Maps not found responses of osu! API into the given error, so that unknown ids are reported as
+/// bad requests instead of internal errors.
+fn map_osu_not_found(osu_err: OsuApiError, not_found: InfluenceBeatmapError) -> AppError {
+    match osu_err {
+        OsuApiError::HTTPError {
+            error: StatusCode::NOT_FOUND,
+            ..
+        } => AppError::from(not_found),
+        osu_err => AppError::from(osu_err),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateInfluenceBeatmapsRequest {
+    from_id: i64,
+    /// Ids of the beatmapsets to attach as a whole
+    #[serde(default)]
+    beatmapset_ids: Vec<i64>,
+    /// Ids of the single difficulties to attach. Listed after the beatmapsets
+    #[serde(default)]
+    beatmap_ids: Vec<i64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/influence/update/beatmaps/",
+    request_body = UpdateInfluenceBeatmapsRequest,
+    responses((status = 200, description = "Influence beatmaps successfully replaced")),
+    security(("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn update_influence_beatmaps(
+    AuthUserId(user_id): AuthUserId,
+    State(state): State<SharedState>,
+    Json(request): Json<UpdateInfluenceBeatmapsRequest>,
+) -> AppResult<()> {
+    let beatmap_count = request.beatmapset_ids.len() + request.beatmap_ids.len();
+    // Checked here as well to not send requests to osu! API for nothing.
+    if beatmap_count > INFLUENCE_BEATMAP_LIMIT {
+        return Err(InfluenceError::TooManyInfluenceBeatmaps(beatmap_count).into());
+    }
+
+    let osu_token = state.app_access_token().await?;
+    let beatmaps = request_influence_beatmaps(&state, osu_token, &request).await?;
+
+    state
+        .postgres()
+        .set_influence_beatmaps(request.from_id, user_id, beatmaps)
+        .await?;
+
+    Ok(())
+}
+
+async fn request_influence_beatmaps(
+    state: &SharedState,
+    osu_token: String,
+    request: &UpdateInfluenceBeatmapsRequest,
+) -> AppResult<Vec<InfluenceBeatmap>> {
+    let mut beatmaps = Vec::with_capacity(request.beatmapset_ids.len() + request.beatmap_ids.len());
+
+    for &beatmapset_id in &request.beatmapset_ids {
+        let beatmapset = state
+            .http()
+            .request_osu_beatmapset(&osu_token, beatmapset_id)
+            .await
+            .map_err(|err| {
+                map_osu_not_found(
+                    err,
+                    InfluenceBeatmapError::BeatmapsetNotFound(beatmapset_id),
+                )
+            })?;
+        beatmaps.push(InfluenceBeatmap::from_beatmapset(beatmapset));
+    }
+
+    for &beatmap_id in &request.beatmap_ids {
+        let beatmap = state
+            .http()
+            .request_osu_beatmap(&osu_token, beatmap_id)
+            .await
+            .map_err(|err| {
+                map_osu_not_found(err, InfluenceBeatmapError::BeatmapNotFound(beatmap_id))
+            })?;
+        let beatmapset = state
+            .http()
+            .request_osu_beatmapset(&osu_token, beatmap.beatmapset_id)
+            .await?;
+        beatmaps.push(InfluenceBeatmap::from_beatmap(beatmap, beatmapset));
+    }
+
+    Ok(beatmaps)
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ReorderInfluenceEntry {
+    from_id: i64,
+    /// New level of the influence. Left unchanged if missing
+    #[schema(minimum = 1, maximum = 9)]
+    #[validate(range(min = 1, max = 9))]
+    level: Option<i32>,
+    /// New info of the influence. Left unchanged if missing
+    info: Option<String>,
+}
+
+impl From<ReorderInfluenceEntry> for InfluenceReorderEntry {
+    fn from(entry: ReorderInfluenceEntry) -> Self {
+        Self {
+            from_id: entry.from_id,
+            influence_level: entry.level,
+            info: entry.info,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ReorderInfluencesRequest {
+    /// Influencers of the session owner in the wanted order. Influences that are not listed are
+    /// placed after the listed ones
+    #[validate]
+    influences: Vec<ReorderInfluenceEntry>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/influence/update/order/",
+    request_body = ReorderInfluencesRequest,
+    responses((status = 200, description = "Influences successfully reordered and updated")),
+    security(("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn reorder_influences(
+    AuthUserId(user_id): AuthUserId,
+    State(state): State<SharedState>,
+    Json(request): Json<ReorderInfluencesRequest>,
+) -> AppResult<()> {
+    request.validate()?;
+
+    let entries = request.influences.into_iter().map(Into::into).collect();
+    state
+        .postgres()
+        .reorder_influences(user_id, entries)
+        .await?;
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/influence/mentions/",
+    responses((status = 200, description = "Influences where the session owner is the influencer, including hidden ones", body = [Influence])),
+    security(("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn get_influence_mentions(
+    AuthUserId(user_id): AuthUserId,
+    State(state): State<SharedState>,
+) -> AppResult<Json<Vec<Influence>>> {
+    let influences = state.postgres().get_user_mentions(user_id).await?;
+
+    Ok(Json(influences))
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateInfluenceAcknowledgementRequest {
+    /// Id of the user that listed the session owner as an influence
+    to_id: i64,
+    acknowledgement: InfluenceAcknowledgement,
+}
+
+#[utoipa::path(
+    post,
+    path = "/influence/update/acknowledgement/",
+    request_body = UpdateInfluenceAcknowledgementRequest,
+    responses((status = 200, description = "Influence acknowledgement successfully updated")),
+    security(("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn update_influence_acknowledgement(
+    AuthUserId(user_id): AuthUserId,
+    State(state): State<SharedState>,
+    Json(request): Json<UpdateInfluenceAcknowledgementRequest>,
+) -> AppResult<()> {
+    // Session owner is the influencer here, unlike the other update endpoints.
+    state
+        .postgres()
+        .update_influence_acknowledgement(user_id, request.to_id, request.acknowledgement)
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetInfluenceHistoryQuery {
+    /// `influencers` returns the changes the user made to their influence list, `influenced`
+    /// returns the changes other users made about them. Defaults to `influencers`
+    #[serde(default)]
+    direction: InfluenceDirection,
+    /// Maximum number of changes to return. Defaults to 50
+    #[param(minimum = 1, maximum = 100)]
+    #[validate(range(min = 1, max = 100))]
+    limit: Option<i64>,
+    /// Number of changes to skip. Defaults to 0
+    #[param(minimum = 0)]
+    #[validate(range(min = 0))]
+    offset: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/influence/history/{user_id}/",
+    params(("user_id", description = "Osu! ID of the user"), GetInfluenceHistoryQuery),
+    responses((status = 200, description = "Influence changes, newest first", body = [InfluenceHistory])),
+    security((), ("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn get_influence_history(
+    MaybeAuthUserId(viewer_id): MaybeAuthUserId,
+    State(state): State<SharedState>,
+    Path(query_user_id): Path<i64>,
+    Query(query): Query<GetInfluenceHistoryQuery>,
+) -> AppResult<Json<Vec<InfluenceHistory>>> {
+    query.validate()?;
+    state
+        .postgres()
+        .ensure_profile_visible(query_user_id, viewer_id)
+        .await?;
+
+    let history = state
+        .postgres()
+        .get_influence_history(
+            query_user_id,
+            query.direction,
+            viewer_id,
+            query.limit.unwrap_or(50),
+            query.offset.unwrap_or(0),
+        )
+        .await?;
+
+    Ok(Json(history))
+}
+
+/// Maximum number of rows accepted in a single influence import.
+const INFLUENCE_IMPORT_MAX_ROWS: usize = 500;
+
+#[derive(Debug, Error)]
+pub enum InfluenceFileError {
+    #[error("Unable to parse CSV file: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Unable to parse JSON file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(
+        "File has {0} rows but at most {} rows can be imported at once",
+        INFLUENCE_IMPORT_MAX_ROWS
+    )]
+    TooManyRows(usize),
+}
+
+impl AppErrorExt for InfluenceFileError {
+    fn user_message(&self) -> String {
+        self.to_string()
+    }
+
+    fn error_type(&self) -> ErrorType {
+        match self {
+            InfluenceFileError::Csv(_) => ErrorType::BadRequestData,
+            InfluenceFileError::Json(_) => ErrorType::BadRequestData,
+            InfluenceFileError::TooManyRows(_) => ErrorType::BadRequestData,
+        }
+    }
+
+    fn log_error(&self) {
+        warn!("{}", self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum InfluenceFileFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InfluenceFileQuery {
+    /// Format of the file. Defaults to `json`
+    #[serde(default)]
+    format: InfluenceFileFormat,
+}
+
+/// Flat representation of an [`Influence`] for CSV files. Tags are joined with `;`.
+#[derive(Debug, Serialize)]
+struct InfluenceCsvRow {
+    from_id: i64,
+    to_id: i64,
+    influence_level: i32,
+    info: Option<String>,
+    position: i32,
+    acknowledgement: InfluenceAcknowledgement,
+    tags: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    modified_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<Influence> for InfluenceCsvRow {
+    fn from(influence: Influence) -> Self {
+        Self {
+            from_id: influence.from_id,
+            to_id: influence.to_id,
+            influence_level: influence.influence_level,
+            info: influence.info,
+            position: influence.position,
+            acknowledgement: influence.acknowledgement,
+            tags: influence.tags.join(";"),
+            created_at: influence.created_at,
+            modified_at: influence.modified_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/influence/export/",
+    params(InfluenceFileQuery),
+    responses((status = 200, description = "Influences of the user as a JSON or CSV file", body = [Influence])),
+    security(("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn export_influences(
+    AuthUserId(user_id): AuthUserId,
+    State(state): State<SharedState>,
+    Query(query): Query<InfluenceFileQuery>,
+) -> AppResult<Response> {
+    let influences = state.postgres().get_user_influencers(user_id).await?;
+
+    let response = match query.format {
+        InfluenceFileFormat::Json => (
+            [(
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"influences.json\"",
+            )],
+            Json(influences),
+        )
+            .into_response(),
+        InfluenceFileFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for influence in influences {
+                writer
+                    .serialize(InfluenceCsvRow::from(influence))
+                    .map_err(InfluenceFileError::from)?;
+            }
+            let file = writer
+                .into_inner()
+                .map_err(|err| InfluenceFileError::from(csv::Error::from(err.into_error())))?;
+
+            (
+                [
+                    (header::CONTENT_TYPE, "text/csv"),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        "attachment; filename=\"influences.csv\"",
+                    ),
+                ],
+                file,
+            )
+                .into_response()
+        }
+    };
+
+    Ok(response)
+}
+
+/// A single influence in an import file. Columns of exported files that are not listed here are
+/// ignored, so exported files can be imported back as they are.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ImportInfluenceRow {
+    from_id: i64,
+    #[schema(minimum = 1, maximum = 9)]
+    #[validate(range(min = 1, max = 9))]
+    influence_level: i32,
+    info: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportInfluenceResult {
+    /// Index of the row in the file, starting from 0
+    row: usize,
+    from_id: i64,
+    /// Change made by this row. Null if the row failed
+    action: Option<InfluenceAction>,
+    /// Reason of the failure. Null if the row succeeded
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportInfluencesResponse {
+    created: usize,
+    updated: usize,
+    failed: usize,
+    rows: Vec<ImportInfluenceResult>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/influence/import/",
+    params(InfluenceFileQuery),
+    request_body(content = [ImportInfluenceRow], description = "JSON array or CSV file with `from_id`, `influence_level` and `info` columns"),
+    responses((status = 200, description = "Per-row report of the import", body = ImportInfluencesResponse)),
+    security(("session_cookie" = []), ("api_key" = [])),
+)]
+#[debug_handler]
+pub async fn import_influences(
+    AuthUserId(user_id): AuthUserId,
+    State(state): State<SharedState>,
+    Query(query): Query<InfluenceFileQuery>,
+    body: String,
+) -> AppResult<Json<ImportInfluencesResponse>> {
+    let rows: Vec<ImportInfluenceRow> = match query.format {
+        InfluenceFileFormat::Json => {
+            serde_json::from_str(&body).map_err(InfluenceFileError::from)?
+        }
+        InfluenceFileFormat::Csv => csv::Reader::from_reader(body.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(InfluenceFileError::from)?,
+    };
+
+    if rows.len() > INFLUENCE_IMPORT_MAX_ROWS {
+        return Err(InfluenceFileError::TooManyRows(rows.len()).into());
+    }
+
+    let mut report = ImportInfluencesResponse {
+        created: 0,
+        updated: 0,
+        failed: 0,
+        rows: Vec::with_capacity(rows.len()),
+    };
+
+    // Invalid rows are reported without reaching the database. Their indexes are kept to merge
+    // the database results back in file order.
+    let mut valid_rows = Vec::new();
+    let mut influences = Vec::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        match row.validate() {
+            Ok(_) => {
+                valid_rows.push((index, row.from_id));
+                influences.push(Influence::new(
+                    row.from_id,
+                    user_id,
+                    row.influence_level,
+                    row.info,
+                ));
+            }
+            Err(err) => report.rows.push(ImportInfluenceResult {
+                row: index,
+                from_id: row.from_id,
+                action: None,
+                error: Some(err.error_message()),
+            }),
+        }
+    }
+
+    let results = state
+        .postgres()
+        .upsert_influences(influences, user_id)
+        .await?;
+
+    for ((index, from_id), result) in valid_rows.into_iter().zip(results) {
+        report.rows.push(match result {
+            Ok(action) => ImportInfluenceResult {
+                row: index,
+                from_id,
+                action: Some(action),
+                error: None,
+            },
+            Err(err) => ImportInfluenceResult {
+                row: index,
+                from_id,
+                action: None,
+                error: Some(err.user_message()),
+            },
+        });
+    }
+    report.rows.sort_by_key(|result| result.row);
+
+    for result in &report.rows {
+        match result.action {
+            Some(InfluenceAction::Create) => report.created += 1,
+            Some(_) => report.updated += 1,
+            None => report.failed += 1,
+        }
+    }
+
+    Ok(Json(report))
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use mi_db::{PrivacySettings, UserError};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_utils::{hide_profile_for_test, init_user_for_test};
+
+    /// 1 -> 2 -> 3 where 3 hides their influence list.
+    async fn init_chain_for_test(db: &PgPool) {
+        for user_id in 1..=3 {
+            init_user_for_test(user_id, db).await;
+        }
+        for (from_id, to_id) in [(1, 2), (2, 3)] {
+            mi_db::insert_influence(Influence::new(from_id, to_id, 1, None), db)
+                .await
+                .unwrap();
+        }
+
+        let settings = PrivacySettings {
+            hide_influences: true,
+            ..Default::default()
+        };
+        mi_db::update_privacy_settings(3, settings, db)
+            .await
+            .unwrap();
+    }
+
+    fn is_profile_hidden(result: AppResult<impl std::fmt::Debug>, user_id: i64) -> bool {
+        matches!(result, Err(AppError::UserError(UserError::ProfileHidden(id))) if id == user_id)
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_get_influences_privacy(db: PgPool) {
+        init_chain_for_test(&db).await;
+        hide_profile_for_test(2, &db).await;
+        let state = SharedState::for_test(db);
+
+        let query = || GetInfluencesQuery {
+            direction: InfluenceDirection::Influencers,
+            sort: Default::default(),
+            order: Default::default(),
+            tag: None,
+            limit: None,
+            offset: None,
+        };
+
+        let result = get_influences(
+            MaybeAuthUserId(None),
+            State(state.clone()),
+            Path(2),
+            Query(query()),
+        )
+        .await;
+        assert!(is_profile_hidden(result, 2));
+
+        let Json(influences) = get_influences(
+            MaybeAuthUserId(Some(1)),
+            State(state.clone()),
+            Path(3),
+            Query(query()),
+        )
+        .await
+        .unwrap();
+        assert!(influences.is_empty());
+
+        let Json(influences) = get_influences(
+            MaybeAuthUserId(Some(3)),
+            State(state),
+            Path(3),
+            Query(query()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(influences.len(), 1);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_get_influence_tree_privacy(db: PgPool) {
+        init_chain_for_test(&db).await;
+        let state = SharedState::for_test(db.clone());
+
+        let query = || GetInfluenceTreeQuery {
+            direction: InfluenceDirection::Influenced,
+            depth: None,
+        };
+
+        // Influence of 2 on 3 is on the hidden list of 3
+        let Json(graph) = get_influence_tree(
+            MaybeAuthUserId(None),
+            State(state.clone()),
+            Path(1),
+            Query(query()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(graph.edges.len(), 1);
+
+        let Json(graph) = get_influence_tree(
+            MaybeAuthUserId(Some(3)),
+            State(state.clone()),
+            Path(1),
+            Query(query()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(graph.edges.len(), 2);
+
+        hide_profile_for_test(1, &db).await;
+        let result =
+            get_influence_tree(MaybeAuthUserId(None), State(state), Path(1), Query(query())).await;
+        assert!(is_profile_hidden(result, 1));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_get_influence_path_privacy(db: PgPool) {
+        init_chain_for_test(&db).await;
+        let state = SharedState::for_test(db.clone());
+
+        let query = || GetInfluencePathQuery {
+            max_depth: None,
+            strongest: true,
+        };
+
+        let Json(path) = get_influence_path(
+            MaybeAuthUserId(Some(1)),
+            State(state.clone()),
+            Path((1, 3)),
+            Query(query()),
+        )
+        .await
+        .unwrap();
+        assert!(path.shortest.is_none());
+        assert!(path.strongest.is_none());
+
+        let Json(path) = get_influence_path(
+            MaybeAuthUserId(Some(3)),
+            State(state.clone()),
+            Path((1, 3)),
+            Query(query()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(path.shortest.unwrap().user_ids, vec![1, 2, 3]);
+        assert_eq!(path.strongest.unwrap().user_ids, vec![1, 2, 3]);
+
+        hide_profile_for_test(3, &db).await;
+        let result = get_influence_path(
+            MaybeAuthUserId(None),
+            State(state),
+            Path((1, 3)),
+            Query(query()),
+        )
+        .await;
+        assert!(is_profile_hidden(result, 3));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_get_influence_history_privacy(db: PgPool) {
+        init_chain_for_test(&db).await;
+        let state = SharedState::for_test(db.clone());
+
+        let query = || GetInfluenceHistoryQuery {
+            direction: InfluenceDirection::Influencers,
+            limit: None,
+            offset: None,
+        };
+
+        let Json(history) = get_influence_history(
+            MaybeAuthUserId(None),
+            State(state.clone()),
+            Path(3),
+            Query(query()),
+        )
+        .await
+        .unwrap();
+        assert!(history.is_empty());
+
+        let Json(history) = get_influence_history(
+            MaybeAuthUserId(Some(3)),
+            State(state.clone()),
+            Path(3),
+            Query(query()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(history.len(), 1);
+
+        hide_profile_for_test(3, &db).await;
+        let result =
+            get_influence_history(MaybeAuthUserId(None), State(state), Path(3), Query(query()))
+                .await;
+        assert!(is_profile_hidden(result, 3));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_export_influences_includes_hidden(db: PgPool) {
+        init_chain_for_test(&db).await;
+        mi_db::update_influence_acknowledgement(2, 3, InfluenceAcknowledgement::Hidden, &db)
+            .await
+            .unwrap();
+        let state = SharedState::for_test(db);
+
+        let query = InfluenceFileQuery {
+            format: InfluenceFileFormat::Json,
+        };
+        let response = export_influences(AuthUserId(3), State(state), Query(query))
+            .await
+            .unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let influences: Vec<Influence> = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(influences.len(), 1);
+        assert_eq!(influences[0].from_id, 2);
+        assert_eq!(
+            influences[0].acknowledgement,
+            InfluenceAcknowledgement::Hidden
+        );
+    }
+}
@@ -2,20 +2,24 @@ use async_trait::async_trait;
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
 use axum_auth::{AuthBearerCustom, Rejection};
+use mi_db::ApiKeyError;
 
 use crate::result::AppResult;
-use crate::SessionError;
 
+pub mod api_key;
 pub mod auth;
 pub mod html;
 pub mod influence;
 pub mod leaderboard;
 pub mod redoc;
+pub mod session;
 pub mod user;
 
-pub fn get_bearer_auth(bearer_auth: BearerAuth) -> AppResult<Option<u128>> {
+/// Returns the personal API key sent as `Authorization: Bearer`, if any.
+pub fn get_bearer_auth(bearer_auth: BearerAuth) -> AppResult<Option<String>> {
     match bearer_auth.0 {
-        Some(token) => Ok(Some(token.parse().map_err(|_| SessionError::CookieError)?)),
+        Some(token) if token.starts_with(api_key::API_KEY_PREFIX) => Ok(Some(token)),
+        Some(_) => Err(ApiKeyError::InvalidApiKey.into()),
         None => Ok(None),
     }
 }
@@ -0,0 +1,130 @@
+use axum::debug_handler;
+use axum::extract::{Path, State};
+use mi_db::auth::Session;
+use serde::Serialize;
+use tower_cookies::{Cookie, Cookies};
+use utoipa::ToSchema;
+
+use crate::result::{AppResult, Json};
+use crate::state::SharedState;
+use crate::{get_session_cookie, SessionAuthUserId, SessionError, COOKIE_NAME};
+
+/// Longest user agent stored with a session. Longer ones are truncated.
+const USER_AGENT_MAX_LENGTH: usize = 256;
+
+/// Creates the session of a user that just logged in.
+pub(crate) fn new_session(state: &SharedState, user_agent: Option<&str>) -> Session {
+    Session {
+        id: state.generate_session_id(),
+        created_at: chrono::Utc::now(),
+        user_agent: user_agent
+            .map(|user_agent| user_agent.chars().take(USER_AGENT_MAX_LENGTH).collect()),
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionResponse {
+    /// Id of the session, used to revoke it
+    id: String,
+    /// Date of the login
+    created_at: chrono::DateTime<chrono::Utc>,
+    /// User agent of the client that logged in
+    user_agent: Option<String>,
+    /// Whether this is the session the request is made with
+    current: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/session/logout",
+    responses((status = 200, description = "Session the request is made with is revoked")),
+    security(("session_cookie" = [])),
+)]
+#[debug_handler]
+pub async fn logout(
+    SessionAuthUserId(_user_id): SessionAuthUserId,
+    cookies: Cookies,
+    State(state): State<SharedState>,
+) -> AppResult<()> {
+    let session_token = get_session_cookie(&cookies)?;
+    state
+        .redis()
+        .delete_session_token(&state.hash_session_token(session_token))
+        .await?;
+
+    cookies.add(Cookie::build(COOKIE_NAME, "").path("/").finish());
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/session/list",
+    responses((status = 200, description = "Active sessions of the session owner, newest first", body = [SessionResponse])),
+    security(("session_cookie" = [])),
+)]
+#[debug_handler]
+pub async fn get_sessions(
+    SessionAuthUserId(user_id): SessionAuthUserId,
+    cookies: Cookies,
+    State(state): State<SharedState>,
+) -> AppResult<Json<Vec<SessionResponse>>> {
+    let current_hash = state.hash_session_token(get_session_cookie(&cookies)?);
+    let sessions = state
+        .redis()
+        .get_user_sessions(user_id)
+        .await?
+        .into_iter()
+        .map(|(session_hash, session)| SessionResponse {
+            id: session.id,
+            created_at: session.created_at,
+            user_agent: session.user_agent,
+            current: session_hash == current_hash,
+        })
+        .collect();
+
+    Ok(Json(sessions))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/session/revoke/{session_id}",
+    responses((status = 200, description = "Session is revoked")),
+    params(("session_id", description = "Id of the session to revoke")),
+    security(("session_cookie" = [])),
+)]
+#[debug_handler]
+pub async fn revoke_session(
+    SessionAuthUserId(user_id): SessionAuthUserId,
+    State(state): State<SharedState>,
+    Path(session_id): Path<String>,
+) -> AppResult<()> {
+    if !state
+        .redis()
+        .delete_user_session(user_id, &session_id)
+        .await?
+    {
+        return Err(SessionError::SessionNotFound(session_id).into());
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/session/revoke",
+    responses((status = 200, description = "Every session of the session owner is revoked, including the one the request is made with")),
+    security(("session_cookie" = [])),
+)]
+#[debug_handler]
+pub async fn revoke_all_sessions(
+    SessionAuthUserId(user_id): SessionAuthUserId,
+    cookies: Cookies,
+    State(state): State<SharedState>,
+) -> AppResult<()> {
+    state.redis().delete_user_sessions(user_id).await?;
+
+    cookies.add(Cookie::build(COOKIE_NAME, "").path("/").finish());
+
+    Ok(())
+}
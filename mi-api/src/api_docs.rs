@@ -1,4 +1,4 @@
-use utoipa::openapi::security::{ApiKeyValue, SecurityScheme};
+use utoipa::openapi::security::{ApiKeyValue, Http, HttpAuthScheme, SecurityScheme};
 use utoipa::{Modify, OpenApi};
 
 use crate::api;
@@ -8,33 +8,102 @@ use crate::api;
     paths(
         api::user::get_user,
         api::user::get_full_user,
+        api::user::search_users,
+        api::user::get_user_by_name,
+        api::user::get_user_name_history,
+        api::user::export_user_data,
+        api::user::delete_user,
+        api::user::refresh_user,
+        api::user::get_privacy_settings,
+        api::user::update_privacy_settings,
         api::user::create_user,
         api::user::update_user,
+        api::user::set_featured_maps,
+        api::user::reorder_featured_maps,
+        api::user::clear_featured_maps,
+        api::user::sync_mapsets,
+        api::user::get_mapsets,
         api::influence::get_influences,
+        api::influence::get_influence_tree,
+        api::influence::get_influence_path,
         api::influence::create_influence,
         api::influence::delete_influence,
+        api::influence::restore_influence,
+        api::influence::get_deleted_influences,
         api::influence::update_influence_level,
         api::influence::update_influence_info,
+        api::influence::get_influence_history,
+        api::influence::get_influence_mentions,
+        api::influence::get_influence_tags,
+        api::influence::update_influence_acknowledgement,
+        api::influence::update_influence_beatmaps,
+        api::influence::reorder_influences,
+        api::influence::export_influences,
+        api::influence::import_influences,
         api::leaderboard::get_user_leaderboard,
+        api::session::logout,
+        api::session::get_sessions,
+        api::session::revoke_session,
+        api::session::revoke_all_sessions,
+        api::api_key::create_api_key,
+        api::api_key::get_api_keys,
+        api::api_key::revoke_api_key,
     ),
     components(schemas(
         mi_db::User,
         mi_db::FullUser,
+        mi_db::UserNameHistory,
+        mi_db::UserDataExport,
+        mi_db::PrivacySettings,
+        mi_db::SocialLink,
+        mi_db::SocialPlatform,
         mi_db::FeaturedMaps,
         mi_db::Maps,
         mi_db::Influence,
+        mi_db::InfluenceAcknowledgement,
+        mi_db::InfluenceDirection,
+        mi_db::InfluenceSort,
+        mi_db::SortOrder,
+        mi_db::InfluenceGraph,
+        mi_db::InfluenceNode,
+        mi_db::InfluenceEdge,
+        mi_db::InfluenceChain,
+        mi_db::InfluenceHistory,
+        mi_db::InfluenceTag,
+        mi_db::InfluenceBeatmap,
+        mi_db::InfluenceAction,
         mi_db::LeaderboardUser,
+        mi_db::ApiKey,
+        mi_db::ApiKeyScope,
         mi_osu_api::Beatmapset,
         mi_osu_api::BeatmapsetNames,
         mi_osu_api::Beatmap ,
         mi_osu_api::Covers ,
         mi_osu_api::BeatmapType,
+        mi_osu_api::UserGroup,
         api::user::CreateUserRequest,
         api::user::UpdateUserRequest,
+        api::user::UserSearchResult,
+        api::user::FeaturedMapsRequest,
+        api::user::SyncMapsetsResponse,
+        api::user::RefreshStatus,
+        api::user::RefreshUserResponse,
+        api::influence::InfluencePathResponse,
         api::influence::InsertInfluenceRequest,
         api::influence::DeleteInfluenceRequest,
         api::influence::UpdateInfluenceLevelRequest,
         api::influence::UpdateInfluenceInfoRequest,
+        api::influence::UpdateInfluenceAcknowledgementRequest,
+        api::influence::UpdateInfluenceBeatmapsRequest,
+        api::influence::ReorderInfluencesRequest,
+        api::influence::ReorderInfluenceEntry,
+        api::influence::InfluenceFileFormat,
+        api::influence::ImportInfluenceRow,
+        api::influence::ImportInfluenceResult,
+        api::influence::ImportInfluencesResponse,
+        api::session::SessionResponse,
+        api::api_key::CreateApiKeyRequest,
+        api::api_key::CreateApiKeyResponse,
     )),
     modifiers(&SecurityAddon)
 )]
@@ -46,11 +115,17 @@ impl Modify for SecurityAddon {
     fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
         if let Some(components) = openapi.components.as_mut() {
             components.add_security_scheme(
-                "api_key",
+                "session_cookie",
                 SecurityScheme::ApiKey(utoipa::openapi::security::ApiKey::Cookie(
                     ApiKeyValue::new("mi-session-token"),
                 )),
-            )
+            );
+            // Personal API keys, accepted wherever the session cookie is except for the requests
+            // that manage the account itself.
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
         }
     }
 }
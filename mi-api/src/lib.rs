@@ -1,12 +1,19 @@
 use std::future::Future;
 
+use api::{get_bearer_auth, BearerAuth};
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
+use axum::http::Method;
+use axum::response::Response;
 use mi_core::AppErrorExt;
-use result::AppResult;
+use mi_db::auth::SESSION_TOKEN_TIMEOUT;
+use mi_db::ApiKeyScope;
+use result::{AppError, AppResult};
 use state::AuthUser;
 use thiserror::Error;
 use tokio::time::Instant;
+use tower_cookies::cookie::time::Duration;
+use tower_cookies::cookie::SameSite;
 use tower_cookies::{Cookie, Cookies};
 use tracing::{error, warn};
 
@@ -15,6 +22,9 @@ pub mod api_docs;
 pub mod request_id;
 pub mod result;
 pub mod state;
+pub mod tasks;
+#[cfg(all(test, feature = "db-tests"))]
+mod test_utils;
 pub mod traces;
 
 const COOKIE_NAME: &str = "mi-session-token";
@@ -27,6 +37,12 @@ pub enum SessionError {
     SessionExpired,
     #[error("Osu auth error: {0}")]
     OsuAuthError(String),
+    #[error("Timed out while waiting for osu! tokens of the user to be refreshed")]
+    OsuTokenRefreshTimedOut,
+    #[error("Session {0} is not found")]
+    SessionNotFound(String),
+    #[error("Login request is invalid or expired, please try logging in again")]
+    InvalidOAuthState,
 }
 
 impl AppErrorExt for SessionError {
@@ -35,6 +51,9 @@ impl AppErrorExt for SessionError {
             SessionError::CookieError => self.to_string(),
             SessionError::SessionExpired => self.to_string(),
             SessionError::OsuAuthError(_) => "Unable to authorize with osu!".to_string(),
+            SessionError::OsuTokenRefreshTimedOut => "Unable to authorize with osu!".to_string(),
+            SessionError::SessionNotFound(_) => self.to_string(),
+            SessionError::InvalidOAuthState => self.to_string(),
         }
     }
 
@@ -43,6 +62,9 @@ impl AppErrorExt for SessionError {
             SessionError::CookieError => mi_core::ErrorType::AuthorizatonError,
             SessionError::SessionExpired => mi_core::ErrorType::AuthorizatonError,
             SessionError::OsuAuthError(_) => mi_core::ErrorType::AuthorizatonError,
+            SessionError::OsuTokenRefreshTimedOut => mi_core::ErrorType::AuthorizatonError,
+            SessionError::SessionNotFound(_) => mi_core::ErrorType::DataNotFound,
+            SessionError::InvalidOAuthState => mi_core::ErrorType::AuthorizatonError,
         }
     }
 
@@ -51,10 +73,25 @@ impl AppErrorExt for SessionError {
             SessionError::CookieError => warn!("{}", self),
             SessionError::SessionExpired => warn!("{}", self),
             SessionError::OsuAuthError(_) => error!("{}", self),
+            SessionError::OsuTokenRefreshTimedOut => warn!("{}", self),
+            SessionError::SessionNotFound(_) => warn!("{}", self),
+            SessionError::InvalidOAuthState => warn!("{}", self),
         }
     }
 }
 
+/// Cookie that carries a session token. It lives as long as the token does on Redis, and it is
+/// neither readable by scripts nor sent over plain HTTP.
+pub(crate) fn session_cookie(session_token: u128) -> Cookie<'static> {
+    Cookie::build(COOKIE_NAME, session_token.to_string())
+        .path("/")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(SESSION_TOKEN_TIMEOUT as i64))
+        .finish()
+}
+
 pub fn get_session_cookie(cookies: &Cookies) -> AppResult<u128> {
     match cookies.get(COOKIE_NAME) {
         Some(cookie) => Ok(cookie
@@ -65,6 +102,69 @@ pub fn get_session_cookie(cookies: &Cookies) -> AppResult<u128> {
     }
 }
 
+fn error_response(err: AppError) -> Response {
+    let box_err: Box<dyn AppErrorExt> = err.into();
+    box_err.log_error();
+    box_err.as_response()
+}
+
+/// Returns the API key sent with the request, if any.
+async fn get_request_api_key<S: Send + Sync>(
+    parts: &mut Parts,
+    state: &S,
+) -> Result<Option<String>, Response> {
+    let bearer_auth = BearerAuth::from_request_parts(parts, state)
+        .await
+        .map_err(|_| SessionError::CookieError.as_response())?;
+
+    get_bearer_auth(bearer_auth).map_err(error_response)
+}
+
+/// Scope an API key needs to make a request with the given method.
+fn required_scope(method: &Method) -> ApiKeyScope {
+    match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS => ApiKeyScope::Read,
+        _ => ApiKeyScope::Write,
+    }
+}
+
+/// Authenticates the request with its API key.
+async fn auth_api_key<S: AuthUser + Sync + Send>(
+    parts: &Parts,
+    state: &S,
+    api_key: &str,
+) -> Result<i64, Response> {
+    state
+        .auth_api_key(api_key, required_scope(&parts.method))
+        .await
+        .map_err(error_response)
+}
+
+/// Authenticates the request with its session cookie.
+async fn auth_session<S: AuthUser + Sync + Send>(
+    parts: &mut Parts,
+    state: &S,
+) -> Result<i64, Response> {
+    let cookies = Cookies::from_request_parts(parts, state)
+        .await
+        .map_err(|_| SessionError::CookieError.as_response())?;
+
+    let auth_res = state.auth_user(&cookies).await;
+    match auth_res {
+        Ok(user_id) => Ok(user_id),
+        Err(err) => {
+            // Set cookie empty if session is expired
+            cookies.add(Cookie::build(COOKIE_NAME, "").path("/").finish());
+
+            Err(error_response(err))
+        }
+    }
+}
+
+/// Id of the user that made the request. The request is authenticated either with the session
+/// cookie or with a personal API key sent as `Authorization: Bearer`.
+///
+/// API keys need the `read` scope for `GET` requests and the `write` scope for the others.
 pub struct AuthUserId(i64);
 
 #[async_trait::async_trait]
@@ -72,21 +172,61 @@ impl<S: AuthUser + Sync + Send> FromRequestParts<S> for AuthUserId {
     type Rejection = axum::response::Response;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(api_key) = get_request_api_key(parts, state).await? {
+            return Ok(AuthUserId(auth_api_key(parts, state, &api_key).await?));
+        }
+
+        Ok(AuthUserId(auth_session(parts, state).await?))
+    }
+}
+
+/// Like [`AuthUserId`], but only accepts the session cookie.
+///
+/// Used by the requests that manage the account itself or export everything stored about it, so
+/// a leaked API key can't be used to create new keys, end sessions, pull the data export or delete
+/// the account.
+pub struct SessionAuthUserId(i64);
+
+#[async_trait::async_trait]
+impl<S: AuthUser + Sync + Send> FromRequestParts<S> for SessionAuthUserId {
+    type Rejection = axum::response::Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(SessionAuthUserId(auth_session(parts, state).await?))
+    }
+}
+
+/// Like [`AuthUserId`], but lets requests without a valid session through as anonymous requests
+/// instead of rejecting them. Requests with an invalid API key are still rejected.
+pub struct MaybeAuthUserId(Option<i64>);
+
+#[async_trait::async_trait]
+impl<S: AuthUser + Sync + Send> FromRequestParts<S> for MaybeAuthUserId {
+    type Rejection = axum::response::Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(api_key) = get_request_api_key(parts, state).await? {
+            return Ok(MaybeAuthUserId(Some(
+                auth_api_key(parts, state, &api_key).await?,
+            )));
+        }
+
         let cookies = Cookies::from_request_parts(parts, state)
             .await
             .map_err(|_| SessionError::CookieError.as_response())?;
 
-        let auth_res = state.auth_user(&cookies).await;
-        match auth_res {
-            Ok(user_id) => Ok(AuthUserId(user_id)),
-            Err(err) => {
+        if cookies.get(COOKIE_NAME).is_none() {
+            return Ok(MaybeAuthUserId(None));
+        }
+
+        match state.auth_user(&cookies).await {
+            Ok(user_id) => Ok(MaybeAuthUserId(Some(user_id))),
+            Err(AppError::SessionError(_)) => {
                 // Set cookie empty if session is expired
                 cookies.add(Cookie::build(COOKIE_NAME, "").path("/").finish());
-
-                let box_err: Box<dyn AppErrorExt> = err.into();
-                box_err.log_error();
-                Err(box_err.as_response())
+                Ok(MaybeAuthUserId(None))
             }
+            Err(err) => Err(error_response(err)),
         }
     }
 }
@@ -1,20 +1,29 @@
 use axum::routing::{delete, get, post};
 use axum::Router;
 use hyper::{Body, Request};
+use mi_api::api::api_key::{create_api_key, get_api_keys, revoke_api_key};
 use mi_api::api::auth::{authorize_from_osu_api, cookie_page, login};
 use mi_api::api::html::html_router;
 use mi_api::api::influence::{
-    create_influence, delete_influence, get_influences, update_influence_info,
+    create_influence, delete_influence, export_influences, get_deleted_influences,
+    get_influence_history, get_influence_mentions, get_influence_path, get_influence_tags,
+    get_influence_tree, get_influences, import_influences, reorder_influences, restore_influence,
+    update_influence_acknowledgement, update_influence_beatmaps, update_influence_info,
     update_influence_level,
 };
 use mi_api::api::leaderboard::get_user_leaderboard;
 use mi_api::api::redoc::redoc;
+use mi_api::api::session::{get_sessions, logout, revoke_all_sessions, revoke_session};
 use mi_api::api::user::{
-    create_user, get_full_user, get_full_user_by_id, get_user, get_user_by_id, update_user,
+    clear_featured_maps, create_user, delete_user, export_user_data, get_full_user,
+    get_full_user_by_id, get_mapsets, get_privacy_settings, get_user, get_user_by_id,
+    get_user_by_name, get_user_name_history, refresh_user, reorder_featured_maps, search_users,
+    set_featured_maps, sync_mapsets, update_privacy_settings, update_user,
 };
 use mi_api::api_docs::ApiDoc;
 use mi_api::request_id::RequestIdGenerator;
 use mi_api::state::SharedState;
+use mi_api::tasks::{purge_deleted_influences, refresh_stale_mapsets, refresh_stale_osu_data};
 use mi_api::traces::init_tracer;
 use tower::ServiceBuilder;
 use tower_cookies::CookieManagerLayer;
@@ -27,33 +36,81 @@ use utoipa_swagger_ui::SwaggerUi;
 fn influence_route() -> Router<SharedState> {
     Router::new()
         .route("/get/:query_user_id", get(get_influences))
+        .route("/tree/:query_user_id", get(get_influence_tree))
+        .route("/path/:from_id/:to_id", get(get_influence_path))
+        .route("/history/:query_user_id", get(get_influence_history))
         .route("/create", post(create_influence))
         .route("/delete/:from_id", delete(delete_influence))
+        .route("/restore/:from_id", post(restore_influence))
+        .route("/deleted", get(get_deleted_influences))
+        .route("/mentions", get(get_influence_mentions))
+        .route("/tags", get(get_influence_tags))
+        .route("/export", get(export_influences))
+        .route("/import", post(import_influences))
         .nest(
             "/update",
             Router::new()
                 .route("/level", post(update_influence_level))
-                .route("/info", post(update_influence_info)),
+                .route("/info", post(update_influence_info))
+                .route("/acknowledgement", post(update_influence_acknowledgement))
+                .route("/beatmaps", post(update_influence_beatmaps))
+                .route("/order", post(reorder_influences)),
         )
 }
 
 fn user_route() -> Router<SharedState> {
     Router::new()
         .route("/get", get(get_user))
+        .route("/search", get(search_users))
         .route("/get/full", get(get_full_user))
         .route("/get/:user_id", get(get_user_by_id))
         .route("/get/:user_id/full", get(get_full_user_by_id))
+        .route("/get/:user_id/names", get(get_user_name_history))
+        .route("/lookup/:user_name", get(get_user_by_name))
+        .route("/:user_id/refresh", post(refresh_user))
         .route("/create", post(create_user))
         .route("/update", post(update_user))
+        .route("/export", get(export_user_data))
+        .route(
+            "/settings/privacy",
+            get(get_privacy_settings).post(update_privacy_settings),
+        )
+        .route("/delete", delete(delete_user))
+        .route("/mapsets/:user_id", get(get_mapsets))
+        .route("/mapsets/sync", post(sync_mapsets))
+        .nest(
+            "/featured_maps",
+            Router::new()
+                .route("/set", post(set_featured_maps))
+                .route("/reorder", post(reorder_featured_maps))
+                .route("/clear", delete(clear_featured_maps)),
+        )
 }
 
 fn leaderboard_route() -> Router<SharedState> {
     Router::new().route("/user", get(get_user_leaderboard))
 }
 
+fn session_route() -> Router<SharedState> {
+    Router::new()
+        .route("/logout", post(logout))
+        .route("/list", get(get_sessions))
+        .route("/revoke", delete(revoke_all_sessions))
+        .route("/revoke/:session_id", delete(revoke_session))
+}
+
+fn api_key_route() -> Router<SharedState> {
+    Router::new()
+        .route("/create", post(create_api_key))
+        .route("/list", get(get_api_keys))
+        .route("/revoke/:key_id", delete(revoke_api_key))
+}
+
 fn api_route() -> Router<SharedState> {
     Router::new()
         .nest("/user", user_route())
+        .nest("/session", session_route())
+        .nest("/key", api_key_route())
         .nest("/influence", influence_route())
         .nest("/leaderboard", leaderboard_route())
 }
@@ -67,6 +124,10 @@ async fn main() {
 
     let app_state = SharedState::new().await;
 
+    tokio::spawn(purge_deleted_influences(app_state.clone()));
+    tokio::spawn(refresh_stale_mapsets(app_state.clone()));
+    tokio::spawn(refresh_stale_osu_data(app_state.clone()));
+
     let app = Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-doc/openapi.json", ApiDoc::openapi()))
         .route("/api-docs/", get(redoc))
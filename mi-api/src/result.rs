@@ -2,13 +2,14 @@ use axum::extract::rejection::JsonRejection;
 use axum::response::IntoResponse;
 use axum_macros::FromRequest;
 use mi_core::AppErrorExt;
-use mi_db::{AuthError, InfluenceError, LockError, UserError};
+use mi_db::{ApiKeyError, AuthError, InfluenceError, LockError, UserError};
 use mi_osu_api::OsuApiError;
 use serde::Serialize;
 use thiserror::Error;
 use tracing::error;
 use validator::ValidationErrors;
 
+use crate::api::influence::{InfluenceBeatmapError, InfluenceFileError};
 use crate::api::leaderboard::LeaderboardError;
 use crate::state::DB_POOL;
 use crate::SessionError;
@@ -53,12 +54,18 @@ pub enum AppError {
     #[error(transparent)]
     AuthError(#[from] AuthError),
     #[error(transparent)]
+    ApiKeyError(#[from] ApiKeyError),
+    #[error(transparent)]
     OsuApiError(#[from] OsuApiError),
     #[error(transparent)]
     UserError(#[from] UserError),
     #[error(transparent)]
     InfluenceError(#[from] InfluenceError),
     #[error(transparent)]
+    InfluenceFileError(#[from] InfluenceFileError),
+    #[error(transparent)]
+    InfluenceBeatmapError(#[from] InfluenceBeatmapError),
+    #[error(transparent)]
     Validation(#[from] ValidationErrors),
     #[error(transparent)]
     LockError(#[from] LockError),
@@ -73,9 +80,12 @@ impl From<AppError> for Box<dyn AppErrorExt> {
             AppError::SessionError(e) => Box::new(e),
             AppError::LeaderboardError(e) => Box::new(e),
             AppError::AuthError(e) => Box::new(e),
+            AppError::ApiKeyError(e) => Box::new(e),
             AppError::OsuApiError(e) => Box::new(e),
             AppError::UserError(e) => Box::new(e),
             AppError::InfluenceError(e) => Box::new(e),
+            AppError::InfluenceFileError(e) => Box::new(e),
+            AppError::InfluenceBeatmapError(e) => Box::new(e),
             AppError::Validation(e) => Box::new(e),
             AppError::LockError(e) => Box::new(e),
             AppError::JsonRejection(e) => Box::new(e),
@@ -1,9 +1,13 @@
 use axum::extract::FromRef;
 use mi_core::future_log_ext::FutureLogExt;
-use mi_osu_api::auth::{access_token, refresh_token, AuthResponseBody};
+use mi_osu_api::auth::{
+    access_token, client_credentials_token, refresh_token, revoke_token, AuthResponseBody,
+    ClientCredentialsResponseBody,
+};
 use mi_osu_api::{
-    request_token_user, request_user, request_user_beatmapsets, BeatmapType, Beatmapset,
-    OsuApiError, User,
+    request_beatmap, request_beatmapset, request_token_user, request_user,
+    request_user_beatmapsets, search_user, Beatmap, BeatmapType, Beatmapset, OsuApiError,
+    SearchResult, User,
 };
 use tracing::instrument;
 
@@ -30,6 +34,13 @@ impl HttpClient {
             .await
     }
 
+    #[instrument(skip(self), fields(elapsed))]
+    pub async fn get_osu_client_credentials_token(
+        &self,
+    ) -> Result<ClientCredentialsResponseBody, OsuApiError> {
+        client_credentials_token(&self.client).log_elapsed().await
+    }
+
     #[instrument(skip(self, code), fields(elapsed))]
     pub async fn get_osu_access_token(
         &self,
@@ -38,6 +49,11 @@ impl HttpClient {
         access_token(&self.client, code).log_elapsed().await
     }
 
+    #[instrument(skip(self, access_token), fields(elapsed))]
+    pub async fn revoke_osu_token(&self, access_token: &str) -> Result<(), OsuApiError> {
+        revoke_token(&self.client, access_token).log_elapsed().await
+    }
+
     #[instrument(skip(self, auth_token), fields(elapsed))]
     pub async fn request_osu_token_user(&self, auth_token: &str) -> Result<User, OsuApiError> {
         request_token_user(&self.client, auth_token)
@@ -56,6 +72,41 @@ impl HttpClient {
             .await
     }
 
+    #[instrument(skip(self, auth_token), fields(elapsed))]
+    pub async fn search_osu_users(
+        &self,
+        auth_token: &str,
+        query: &str,
+        page: i64,
+    ) -> Result<SearchResult, OsuApiError> {
+        search_user(&self.client, auth_token, query, page)
+            .log_elapsed()
+            .await
+            .map(|wrapper| wrapper.user)
+    }
+
+    #[instrument(skip(self, auth_token), fields(elapsed))]
+    pub async fn request_osu_beatmap(
+        &self,
+        auth_token: &str,
+        beatmap_id: i64,
+    ) -> Result<Beatmap, OsuApiError> {
+        request_beatmap(&self.client, auth_token, beatmap_id)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self, auth_token), fields(elapsed))]
+    pub async fn request_osu_beatmapset(
+        &self,
+        auth_token: &str,
+        beatmapset_id: i64,
+    ) -> Result<Beatmapset, OsuApiError> {
+        request_beatmapset(&self.client, auth_token, beatmapset_id)
+            .log_elapsed()
+            .await
+    }
+
     #[instrument(skip(self, auth_token), fields(elapsed))]
     pub async fn get_all_user_mapsets(
         &self,
@@ -1,41 +1,99 @@
 pub mod http;
 mod postgres;
 pub mod redis;
+mod refresh;
 
+use std::fmt::{self, Debug};
+use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use hmac::{Hmac, Mac};
+use mi_core::AppErrorExt;
+use mi_db::auth::{AuthError, SessionOwner, SESSION_TOKEN_TIMEOUT};
+use mi_db::{ApiKeyError, ApiKeyScope};
+use mi_osu_api::OsuApiError;
 use once_cell::sync::OnceCell;
-use parking_lot::{Mutex, MutexGuard};
-use rand_chacha::rand_core::{OsRng, RngCore, SeedableRng};
-use rand_chacha::ChaCha8Rng;
+use rand_chacha::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
 use tower_cookies::Cookies;
 
 pub use self::http::HttpClient;
 pub use self::postgres::PgDb;
 pub use self::redis::RedisDb;
-use crate::result::AppResult;
-use crate::{get_session_cookie, SessionError};
+pub use self::refresh::RefreshPolicy;
+use crate::api::api_key::API_KEY_PREFIX;
+use crate::result::{AppError, AppResult};
+use crate::{get_session_cookie, session_cookie, SessionError};
 
 pub static DB_POOL: OnceCell<sqlx::PgPool> = OnceCell::new();
 
+/// Seconds the cached application token is expired before its actual expiry.
+const APP_TOKEN_EXPIRY_MARGIN: usize = 60;
+/// How long a request waits for another request to refresh the osu! tokens of the same user.
+const OSU_TOKEN_REFRESH_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+const OSU_TOKEN_REFRESH_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Seconds after which a session token is replaced with a new one on its next use.
+const SESSION_TOKEN_ROTATION_INTERVAL: usize = 3600; // 1 hour
+/// Shortest accepted `MI_SESSION_SECRET`, in bytes.
+const SESSION_SECRET_MIN_LENGTH: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Key of the keyed hash session tokens are stored with. Read from `MI_SESSION_SECRET`.
+#[derive(Clone)]
+struct SessionKey(Arc<[u8]>);
+
+impl SessionKey {
+    fn from_env() -> Self {
+        let secret = std::env::var("MI_SESSION_SECRET")
+            .expect("Environment variable MI_SESSION_SECRET is not set.");
+        assert!(
+            secret.len() >= SESSION_SECRET_MIN_LENGTH,
+            "Environment variable MI_SESSION_SECRET must be at least {} bytes long.",
+            SESSION_SECRET_MIN_LENGTH
+        );
+
+        Self(secret.into_bytes().into())
+    }
+}
+
+impl Debug for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SessionKey(..)")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SharedState {
     http_client: HttpClient,
     redis: RedisDb,
     postgres: PgDb,
-    random: Arc<Mutex<ChaCha8Rng>>,
+    refresh_policy: RefreshPolicy,
+    session_key: SessionKey,
 }
 
 impl SharedState {
     pub async fn new() -> Self {
-        let random = ChaCha8Rng::seed_from_u64(OsRng.next_u64());
-        let random = Arc::new(Mutex::new(random));
-
         Self {
             http_client: HttpClient::new(),
             redis: RedisDb::new().await,
             postgres: PgDb::new().await,
-            random,
+            refresh_policy: RefreshPolicy::from_env(),
+            session_key: SessionKey::from_env(),
+        }
+    }
+
+    /// State for tests, built around the database of the test. Redis is only connected to once it
+    /// is used, so tests that don't need it can run without it.
+    #[cfg(all(test, feature = "db-tests"))]
+    pub(crate) fn for_test(pool: sqlx::PgPool) -> Self {
+        Self {
+            http_client: HttpClient::new(),
+            redis: RedisDb::for_test(),
+            postgres: PgDb::for_test(pool),
+            refresh_policy: RefreshPolicy::default(),
+            session_key: SessionKey(b"a session secret that is only used in tests"[..].into()),
         }
     }
 
@@ -43,15 +101,67 @@ impl SharedState {
     ///
     /// If successful, returns user's Osu! id
     /// If user's session token is expired or it does not exist in DB, redirects user to /login
+    ///
+    /// Tokens older than [`SESSION_TOKEN_ROTATION_INTERVAL`] are replaced with a new one that is
+    /// sent back with the response, so sessions of active users don't expire.
     pub async fn auth_user(&self, cookies: &Cookies) -> AppResult<i64> {
         let token = get_session_cookie(cookies)?;
-        let auth_res = self.redis().get_user_id(token).await;
+        let session_hash = self.hash_session_token(token);
+        let owner = match self.redis().get_session_owner(&session_hash).await {
+            Ok(owner) => owner,
+            Err(AuthError::ValueNotFound { .. }) => {
+                return Err(SessionError::SessionExpired.into());
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        if owner.expires_in + SESSION_TOKEN_ROTATION_INTERVAL < SESSION_TOKEN_TIMEOUT {
+            // The current token is still valid, a failed rotation is retried on the next request.
+            if let Err(err) = self
+                .rotate_session_token(cookies, &owner, &session_hash)
+                .await
+            {
+                Box::<dyn AppErrorExt>::from(err).log_error();
+            }
+        }
 
-        if let Err(mi_db::auth::AuthError::ValueNotFound { .. }) = auth_res {
-            Err(SessionError::SessionExpired.into())
-        } else {
-            Ok(auth_res?)
+        Ok(owner.user_id)
+    }
+
+    async fn rotate_session_token(
+        &self,
+        cookies: &Cookies,
+        owner: &SessionOwner,
+        session_hash: &str,
+    ) -> AppResult<()> {
+        let new_token = self.generate_session_token();
+        let new_session_hash = self.hash_session_token(new_token);
+
+        let is_rotated = self
+            .redis()
+            .rotate_session_token(owner, session_hash, &new_session_hash)
+            .await?;
+        if is_rotated {
+            cookies.add(session_cookie(new_token));
         }
+
+        Ok(())
+    }
+
+    /// Authenticates user from a personal API key
+    ///
+    /// If successful, returns Osu! id of the owner of the key
+    pub async fn auth_api_key(&self, api_key: &str, scope: ApiKeyScope) -> AppResult<i64> {
+        let api_key = self
+            .postgres()
+            .authenticate_api_key(&hash_api_key(api_key))
+            .await?;
+
+        if !api_key.has_scope(scope) {
+            return Err(ApiKeyError::MissingScope(scope).into());
+        }
+
+        Ok(api_key.user_id)
     }
 
     pub fn http(&self) -> &HttpClient {
@@ -66,16 +176,214 @@ impl SharedState {
         &self.postgres
     }
 
-    pub fn random(&self) -> MutexGuard<ChaCha8Rng> {
-        self.random.lock()
+    pub fn refresh_policy(&self) -> &RefreshPolicy {
+        &self.refresh_policy
+    }
+
+    /// Returns an osu! access token that belongs to the application, requesting a new one if the
+    /// cached token is expired.
+    ///
+    /// Used for osu! requests that don't need the user's own token, so they also work for API key
+    /// callers that have no osu! session.
+    pub async fn app_access_token(&self) -> AppResult<String> {
+        match self.redis().get_app_access_token().await {
+            Ok(token) => return Ok(token),
+            Err(mi_db::auth::AuthError::ValueNotFound { .. }) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let response = self.http().get_osu_client_credentials_token().await?;
+        // Expire the cached token a bit earlier so it is never used right at its expiry
+        let expires_in = (response.expires_in as usize).saturating_sub(APP_TOKEN_EXPIRY_MARGIN);
+        self.redis()
+            .set_app_access_token(&response.access_token, expires_in.max(1))
+            .await?;
+
+        Ok(response.access_token)
+    }
+
+    /// Returns the osu! access token of a user, refreshing it with the stored refresh token if it
+    /// is expired.
+    pub async fn osu_access_token(&self, user_id: i64) -> AppResult<String> {
+        match self.redis().get_access_token(user_id).await {
+            Ok(token) => Ok(token),
+            Err(AuthError::ValueNotFound { .. }) => self.refresh_osu_tokens(user_id, None).await,
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Runs an osu! request on behalf of a user.
+    ///
+    /// If osu! rejects the access token before it expires on our side, the tokens are refreshed
+    /// and the request is retried once with the new access token.
+    pub async fn with_osu_token<T, F, Fut>(&self, user_id: i64, request: F) -> AppResult<T>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = AppResult<T>>,
+    {
+        let osu_token = self.osu_access_token(user_id).await?;
+
+        match request(osu_token.clone()).await {
+            Err(AppError::OsuApiError(OsuApiError::HTTPError {
+                error: reqwest::StatusCode::UNAUTHORIZED,
+                ..
+            })) => {
+                let osu_token = self.refresh_osu_tokens(user_id, Some(&osu_token)).await?;
+                request(osu_token).await
+            }
+            result => result,
+        }
+    }
+
+    /// Replaces the osu! tokens of a user with new ones obtained through the stored refresh
+    /// token and returns the new access token.
+    ///
+    /// osu! accepts a refresh token only once, so concurrent requests of the same user don't
+    /// refresh the tokens themselves but wait for the one that holds the refresh lock.
+    /// `stale_token` is the access token osu! rejected, if any. A different access token found
+    /// in Redis means the tokens were already refreshed by another request.
+    async fn refresh_osu_tokens(
+        &self,
+        user_id: i64,
+        stale_token: Option<&str>,
+    ) -> AppResult<String> {
+        if !self.redis().lock_osu_token_refresh(user_id).await? {
+            return self.wait_for_osu_token_refresh(user_id, stale_token).await;
+        }
+
+        let result = self.refresh_osu_tokens_locked(user_id, stale_token).await;
+        self.redis().unlock_osu_token_refresh(user_id).await?;
+
+        result
     }
 
+    async fn refresh_osu_tokens_locked(
+        &self,
+        user_id: i64,
+        stale_token: Option<&str>,
+    ) -> AppResult<String> {
+        // Another request might have refreshed the tokens right before we took the lock.
+        match self.redis().get_access_token(user_id).await {
+            Ok(token) if Some(token.as_str()) != stale_token => return Ok(token),
+            Ok(_) | Err(AuthError::ValueNotFound { .. }) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let refresh_token = match self.redis().get_refresh_token(user_id).await {
+            Ok(token) => token,
+            Err(AuthError::ValueNotFound { .. }) => {
+                return Err(SessionError::SessionExpired.into());
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let response = match self.http().get_osu_refresh_token(refresh_token).await {
+            Ok(response) => response,
+            // The refresh token is revoked or expired, user has to log in again.
+            Err(OsuApiError::HTTPError { error, .. }) if error.is_client_error() => {
+                self.redis().delete_osu_tokens(user_id).await?;
+                return Err(SessionError::SessionExpired.into());
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        self.redis()
+            .set_osu_tokens(
+                user_id,
+                &response.access_token,
+                &response.refresh_token,
+                response.expires_in as usize,
+            )
+            .await?;
+
+        Ok(response.access_token)
+    }
+
+    /// Waits for the request that holds the refresh lock to store the new tokens.
+    ///
+    /// Stops early with [`SessionError::SessionExpired`] if the lock is released and the tokens
+    /// are gone, which happens when osu! rejects the refresh token.
+    async fn wait_for_osu_token_refresh(
+        &self,
+        user_id: i64,
+        stale_token: Option<&str>,
+    ) -> AppResult<String> {
+        let started_at = Instant::now();
+
+        while started_at.elapsed() < OSU_TOKEN_REFRESH_WAIT_TIMEOUT {
+            tokio::time::sleep(OSU_TOKEN_REFRESH_WAIT_POLL_INTERVAL).await;
+
+            // The lock is checked before the tokens so a refresh that finishes in between is not
+            // mistaken for a failed one
+            let refresh_locked = self.redis().is_osu_token_refresh_locked(user_id).await?;
+
+            match self.redis().get_access_token(user_id).await {
+                Ok(token) if Some(token.as_str()) != stale_token => return Ok(token),
+                Ok(_) | Err(AuthError::ValueNotFound { .. }) => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            if refresh_locked {
+                continue;
+            }
+
+            match self.redis().get_refresh_token(user_id).await {
+                Err(AuthError::ValueNotFound { .. }) => {
+                    return Err(SessionError::SessionExpired.into());
+                }
+                // The refresh failed for another reason, the tokens might still be refreshed by
+                // a later request
+                Ok(_) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Err(SessionError::OsuTokenRefreshTimedOut.into())
+    }
+
+    /// Generates a session token, straight from the OS random number generator.
     pub fn generate_session_token(&self) -> u128 {
-        let mut random = self.random();
         let mut u128_pool = [0u8; 16];
-        random.fill_bytes(&mut u128_pool);
+        OsRng.fill_bytes(&mut u128_pool);
         u128::from_le_bytes(u128_pool)
     }
+
+    /// Hashes a session token the way it is stored in Redis.
+    ///
+    /// The hash is keyed with `MI_SESSION_SECRET`, so the tokens can't be recovered from the
+    /// stored hashes without the secret.
+    pub fn hash_session_token(&self, session_token: u128) -> String {
+        let mut mac = HmacSha256::new_from_slice(&self.session_key.0)
+            .expect("HMAC can take a key of any size");
+        mac.update(session_token.to_string().as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Generates a personal API key. Keys are long lived, so they are taken straight from the OS
+    /// random number generator.
+    pub fn generate_api_key(&self) -> String {
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        format!("{}{}", API_KEY_PREFIX, hex::encode(key))
+    }
+
+    /// Generates the `state` parameter of an osu! login, the value that ties the login callback
+    /// to the browser that started the login.
+    pub fn generate_oauth_state(&self) -> String {
+        let mut oauth_state = [0u8; 32];
+        OsRng.fill_bytes(&mut oauth_state);
+        hex::encode(oauth_state)
+    }
+
+    /// Generates the public id of a session, the one listed to the user instead of its token.
+    pub fn generate_session_id(&self) -> String {
+        format!("{:016x}", OsRng.next_u64())
+    }
+}
+
+/// Hashes a personal API key the way it is stored in the database.
+pub fn hash_api_key(api_key: &str) -> String {
+    hex::encode(Sha256::digest(api_key.as_bytes()))
 }
 
 impl AsRef<PgDb> for SharedState {
@@ -94,6 +402,9 @@ impl AsRef<RedisDb> for SharedState {
 pub trait AuthUser {
     /// Returns user's Osu! id if user is authenticated
     async fn auth_user(&self, cookie: &Cookies) -> AppResult<i64>;
+
+    /// Returns Osu! id of the owner of the API key if the key is valid and has the given scope
+    async fn auth_api_key(&self, api_key: &str, scope: ApiKeyScope) -> AppResult<i64>;
 }
 
 #[async_trait::async_trait]
@@ -101,4 +412,120 @@ impl AuthUser for SharedState {
     async fn auth_user(&self, cookies: &Cookies) -> AppResult<i64> {
         self.auth_user(cookies).await
     }
+
+    async fn auth_api_key(&self, api_key: &str, scope: ApiKeyScope) -> AppResult<i64> {
+        self.auth_api_key(api_key, scope).await
+    }
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use sqlx::PgPool;
+
+    use super::*;
+
+    fn unauthorized() -> AppError {
+        OsuApiError::HTTPError {
+            body: String::new(),
+            error: reqwest::StatusCode::UNAUTHORIZED,
+        }
+        .into()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_with_osu_token_retry(db: PgPool) {
+        let state = SharedState::for_test(db);
+        let user_id = 9_000_001;
+        state
+            .redis()
+            .set_osu_tokens(user_id, "old_access", "refresh", 3600)
+            .await
+            .unwrap();
+
+        // osu! rejects the old token while another request has already refreshed the tokens, so
+        // the retry uses the new token without going to osu!
+        let calls = AtomicUsize::new(0);
+        let result = state
+            .with_osu_token(user_id, |osu_token| {
+                let state = &state;
+                let calls = &calls;
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    if osu_token == "old_access" {
+                        state
+                            .redis()
+                            .set_osu_tokens(user_id, "new_access", "new_refresh", 3600)
+                            .await?;
+                        return Err(unauthorized());
+                    }
+                    Ok(osu_token)
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(result, "new_access");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Requests are retried only once
+        let calls = AtomicUsize::new(0);
+        let result = state
+            .with_osu_token(user_id, |osu_token| {
+                let state = &state;
+                let calls = &calls;
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    let new_token = format!("{}_next", osu_token);
+                    state
+                        .redis()
+                        .set_osu_tokens(user_id, &new_token, "refresh", 3600)
+                        .await?;
+                    Err::<(), _>(unauthorized())
+                }
+            })
+            .await;
+        assert!(matches!(result, Err(AppError::OsuApiError(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        state.redis().delete_osu_tokens(user_id).await.unwrap();
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_osu_token_refresh_wait_session_expired(db: PgPool) {
+        let state = SharedState::for_test(db);
+        let user_id = 9_000_002;
+        state
+            .redis()
+            .set_osu_tokens(user_id, "old_access", "refresh", 3600)
+            .await
+            .unwrap();
+
+        // Another request holds the lock and finds out the refresh token is revoked
+        assert!(state.redis().lock_osu_token_refresh(user_id).await.unwrap());
+        let other_state = state.clone();
+        let other_request = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            other_state
+                .redis()
+                .delete_osu_tokens(user_id)
+                .await
+                .unwrap();
+            other_state
+                .redis()
+                .unlock_osu_token_refresh(user_id)
+                .await
+                .unwrap();
+        });
+
+        let started_at = Instant::now();
+        let result = state.refresh_osu_tokens(user_id, Some("old_access")).await;
+        other_request.await.unwrap();
+
+        assert!(matches!(
+            result,
+            Err(AppError::SessionError(SessionError::SessionExpired))
+        ));
+        assert!(started_at.elapsed() < OSU_TOKEN_REFRESH_WAIT_TIMEOUT);
+    }
 }
@@ -1,6 +1,12 @@
 use axum::extract::FromRef;
+use chrono::{DateTime, Utc};
 use mi_core::future_log_ext::FutureLogExt;
-use mi_db::{FeaturedMaps, FullUser, Influence, InfluenceError, LeaderboardUser, User, UserError};
+use mi_db::{
+    ApiKey, ApiKeyError, ApiKeyScope, FeaturedMaps, FullUser, Influence, InfluenceAcknowledgement,
+    InfluenceAction, InfluenceBeatmap, InfluenceChain, InfluenceDirection, InfluenceError,
+    InfluenceGraph, InfluenceHistory, InfluenceListOptions, InfluenceReorderEntry, InfluenceTag,
+    LeaderboardUser, PrivacySettings, SocialLink, User, UserDataExport, UserError, UserNameHistory,
+};
 use mi_osu_api::Beatmapset;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
@@ -30,30 +36,120 @@ impl PgDb {
         }
     }
 
+    #[cfg(all(test, feature = "db-tests"))]
+    pub(crate) fn for_test(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
     #[instrument(skip(self), fields(elapsed), ret)]
     pub async fn get_user(&self, user_id: i64) -> Result<User, UserError> {
         mi_db::get_user(user_id, &self.pool).log_elapsed().await
     }
 
+    #[instrument(skip(self), fields(elapsed))]
+    pub async fn search_users(
+        &self,
+        query: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<User>, UserError> {
+        mi_db::search_users(query, limit, offset, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
     #[instrument(skip(self), fields(elapsed), ret)]
-    pub async fn get_full_user(&self, user_id: i64) -> Result<FullUser, UserError> {
-        mi_db::get_full_user(user_id, &self.pool)
+    pub async fn get_user_by_name(&self, user_name: &str) -> Result<User, UserError> {
+        mi_db::get_user_by_name(user_name, &self.pool)
             .log_elapsed()
             .await
     }
 
     #[instrument(skip(self), fields(elapsed), ret)]
-    pub async fn insert_user(&self, user: User) -> Result<User, UserError> {
-        mi_db::init_user(user, &self.pool).log_elapsed().await
+    pub async fn get_user_name_history(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<UserNameHistory>, UserError> {
+        mi_db::get_user_name_history(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_users_with_stale_osu_data(
+        &self,
+        stale_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<i64>, UserError> {
+        mi_db::get_users_with_stale_osu_data(stale_before, limit, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed))]
+    pub async fn get_user_data_export(&self, user_id: i64) -> Result<UserDataExport, UserError> {
+        mi_db::get_user_data_export(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn delete_user(&self, user_id: i64) -> Result<(), UserError> {
+        mi_db::delete_user(user_id, &self.pool).log_elapsed().await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn update_user_social_links(
+        &self,
+        social_links: &[SocialLink],
+        user_id: i64,
+    ) -> Result<(), UserError> {
+        mi_db::update_user_social_links(social_links, user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_privacy_settings(&self, user_id: i64) -> Result<PrivacySettings, UserError> {
+        mi_db::get_privacy_settings(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn ensure_profile_visible(
+        &self,
+        user_id: i64,
+        viewer_id: Option<i64>,
+    ) -> Result<(), UserError> {
+        mi_db::ensure_profile_visible(user_id, viewer_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn update_privacy_settings(
+        &self,
+        user_id: i64,
+        settings: PrivacySettings,
+    ) -> Result<(), UserError> {
+        mi_db::update_privacy_settings(user_id, settings, &self.pool)
+            .log_elapsed()
+            .await
     }
 
     #[instrument(skip(self), fields(elapsed), ret)]
-    pub async fn update_user_name(&self, user_name: &str, user_id: i64) -> Result<(), UserError> {
-        mi_db::update_user_name(user_name, user_id, &self.pool)
+    pub async fn get_full_user(&self, user_id: i64) -> Result<FullUser, UserError> {
+        mi_db::get_full_user(user_id, &self.pool)
             .log_elapsed()
             .await
     }
 
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn insert_user(&self, user: User) -> Result<User, UserError> {
+        mi_db::init_user(user, &self.pool).log_elapsed().await
+    }
+
     #[instrument(skip(self), fields(elapsed), ret)]
     pub async fn update_user_picture(
         &self,
@@ -86,6 +182,66 @@ impl PgDb {
             .await
     }
 
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_user_mentions(&self, user_id: i64) -> Result<Vec<Influence>, InfluenceError> {
+        mi_db::get_all_influences_by_from_id(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_influences_page(
+        &self,
+        user_id: i64,
+        options: InfluenceListOptions,
+    ) -> Result<Vec<Influence>, InfluenceError> {
+        mi_db::get_influences_page(user_id, options, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_influence_graph(
+        &self,
+        user_id: i64,
+        direction: InfluenceDirection,
+        max_depth: i32,
+        max_edges: i64,
+        viewer_id: Option<i64>,
+    ) -> Result<InfluenceGraph, InfluenceError> {
+        mi_db::get_influence_graph(
+            user_id, direction, max_depth, max_edges, viewer_id, &self.pool,
+        )
+        .log_elapsed()
+        .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_shortest_influence_chain(
+        &self,
+        from_id: i64,
+        to_id: i64,
+        max_depth: i32,
+        viewer_id: Option<i64>,
+    ) -> Result<Option<InfluenceChain>, InfluenceError> {
+        mi_db::get_shortest_influence_chain(from_id, to_id, max_depth, viewer_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_strongest_influence_chain(
+        &self,
+        from_id: i64,
+        to_id: i64,
+        max_depth: i32,
+        viewer_id: Option<i64>,
+    ) -> Result<Option<InfluenceChain>, InfluenceError> {
+        mi_db::get_strongest_influence_chain(from_id, to_id, max_depth, viewer_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
     #[instrument(skip(self), fields(elapsed), ret)]
     pub async fn insert_influence(&self, influence: Influence) -> Result<(), InfluenceError> {
         mi_db::insert_influence(influence, &self.pool)
@@ -93,14 +249,66 @@ impl PgDb {
             .await
     }
 
+    #[instrument(skip(self, influences), fields(elapsed), ret)]
+    pub async fn upsert_influences(
+        &self,
+        influences: Vec<Influence>,
+        actor_id: i64,
+    ) -> Result<Vec<Result<InfluenceAction, InfluenceError>>, InfluenceError> {
+        mi_db::upsert_influences(influences, actor_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn update_influence_acknowledgement(
+        &self,
+        from_id: i64,
+        to_id: i64,
+        acknowledgement: InfluenceAcknowledgement,
+    ) -> Result<(), InfluenceError> {
+        mi_db::update_influence_acknowledgement(from_id, to_id, acknowledgement, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_influence_tags(&self) -> Result<Vec<InfluenceTag>, InfluenceError> {
+        mi_db::get_influence_tags(&self.pool).log_elapsed().await
+    }
+
+    #[instrument(skip(self, beatmaps), fields(elapsed), ret)]
+    pub async fn set_influence_beatmaps(
+        &self,
+        from_id: i64,
+        to_id: i64,
+        beatmaps: Vec<InfluenceBeatmap>,
+    ) -> Result<(), InfluenceError> {
+        mi_db::set_influence_beatmaps(from_id, to_id, beatmaps, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self, entries), fields(elapsed), ret)]
+    pub async fn reorder_influences(
+        &self,
+        to_id: i64,
+        entries: Vec<InfluenceReorderEntry>,
+    ) -> Result<(), InfluenceError> {
+        mi_db::reorder_influences(to_id, entries, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
     #[instrument(skip(self), fields(elapsed), ret)]
     pub async fn update_influence_level(
         &self,
         from_id: i64,
         to_id: i64,
         level: i32,
+        actor_id: i64,
     ) -> Result<(), InfluenceError> {
-        mi_db::update_influence_level(from_id, to_id, level, &self.pool)
+        mi_db::update_influence_level(from_id, to_id, level, actor_id, &self.pool)
             .log_elapsed()
             .await
     }
@@ -111,15 +319,65 @@ impl PgDb {
         from_id: i64,
         to_id: i64,
         info: Option<&str>,
+        tags: Option<&[String]>,
+        actor_id: i64,
+    ) -> Result<(), InfluenceError> {
+        mi_db::update_influence_info(from_id, to_id, info, tags, actor_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn delete_influence(
+        &self,
+        from_id: i64,
+        to_id: i64,
+        actor_id: i64,
     ) -> Result<(), InfluenceError> {
-        mi_db::update_influence_info(from_id, to_id, info, &self.pool)
+        mi_db::delete_influence(from_id, to_id, actor_id, &self.pool)
             .log_elapsed()
             .await
     }
 
     #[instrument(skip(self), fields(elapsed), ret)]
-    pub async fn delete_influence(&self, from_id: i64, to_id: i64) -> Result<(), InfluenceError> {
-        mi_db::delete_influence(from_id, to_id, &self.pool)
+    pub async fn restore_influence(
+        &self,
+        from_id: i64,
+        to_id: i64,
+        actor_id: i64,
+    ) -> Result<(), InfluenceError> {
+        mi_db::restore_influence(from_id, to_id, actor_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed))]
+    pub async fn get_deleted_influences(
+        &self,
+        to_id: i64,
+    ) -> Result<Vec<Influence>, InfluenceError> {
+        mi_db::get_deleted_influences(to_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn purge_deleted_influences(&self) -> Result<u64, InfluenceError> {
+        mi_db::purge_deleted_influences(&self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_influence_history(
+        &self,
+        user_id: i64,
+        direction: InfluenceDirection,
+        viewer_id: Option<i64>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<InfluenceHistory>, InfluenceError> {
+        mi_db::get_influence_history(user_id, direction, viewer_id, limit, offset, &self.pool)
             .log_elapsed()
             .await
     }
@@ -142,6 +400,59 @@ impl PgDb {
             .await
     }
 
+    #[instrument(skip(self), fields(elapsed))]
+    pub async fn set_user_featured_maps(
+        &self,
+        user_id: i64,
+        featured_map_ids: &[i64],
+    ) -> Result<FeaturedMaps, UserError> {
+        mi_db::set_user_featured_maps(user_id, featured_map_ids, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed))]
+    pub async fn reorder_user_featured_maps(
+        &self,
+        user_id: i64,
+        featured_map_ids: &[i64],
+    ) -> Result<FeaturedMaps, UserError> {
+        mi_db::reorder_user_featured_maps(user_id, featured_map_ids, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn clear_user_featured_maps(&self, user_id: i64) -> Result<(), UserError> {
+        mi_db::clear_user_featured_maps(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_user_mapsets_modified_at(
+        &self,
+        user_id: i64,
+    ) -> Result<Option<DateTime<Utc>>, UserError> {
+        mi_db::get_user_mapsets_modified_at(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn get_users_with_stale_mapsets(&self, limit: i64) -> Result<Vec<i64>, UserError> {
+        mi_db::get_users_with_stale_mapsets(limit, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn record_osu_data_refresh_attempt(&self, user_id: i64) -> Result<(), UserError> {
+        mi_db::record_osu_data_refresh_attempt(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
     #[instrument(skip(self), fields(elapsed), ret)]
     pub async fn get_user_mapsets(&self, user_id: i64) -> Result<Vec<Beatmapset>, UserError> {
         mi_db::get_user_mapsets(user_id, &self.pool)
@@ -167,6 +478,41 @@ impl PgDb {
             .await
             .map_err(|e| e.into())
     }
+
+    #[instrument(skip(self, key_hash), fields(elapsed), ret)]
+    pub async fn insert_api_key(
+        &self,
+        user_id: i64,
+        name: &str,
+        key_hash: &str,
+        scopes: &[ApiKeyScope],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<ApiKey, ApiKeyError> {
+        mi_db::insert_api_key(user_id, name, key_hash, scopes, expires_at, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed))]
+    pub async fn get_user_api_keys(&self, user_id: i64) -> Result<Vec<ApiKey>, ApiKeyError> {
+        mi_db::get_user_api_keys(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self, key_hash), fields(elapsed))]
+    pub async fn authenticate_api_key(&self, key_hash: &str) -> Result<ApiKey, ApiKeyError> {
+        mi_db::authenticate_api_key(key_hash, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn delete_api_key(&self, user_id: i64, key_id: i64) -> Result<(), ApiKeyError> {
+        mi_db::delete_api_key(user_id, key_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
 }
 
 impl FromRef<SharedState> for PgDb {
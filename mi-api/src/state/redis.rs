@@ -1,6 +1,6 @@
 use axum::extract::FromRef;
 use mi_core::future_log_ext::FutureLogExt;
-use mi_db::auth::AuthResult;
+use mi_db::auth::{AuthResult, Session, SessionOwner};
 use mi_db::user_lock::LockError;
 use mi_db::RedisPool;
 use tracing::instrument;
@@ -25,9 +25,39 @@ impl RedisDb {
         Self { pool }
     }
 
-    #[instrument(skip(self, session_token), fields(elapsed), ret)]
-    pub async fn get_user_id(&self, session_token: u128) -> AuthResult<i64> {
-        mi_db::get_user_id(session_token, &self.pool)
+    #[cfg(all(test, feature = "db-tests"))]
+    pub(crate) fn for_test() -> Self {
+        let url = std::env::var("MI_TEST_REDIS_URL")
+            .unwrap_or_else(|_| "redis://localhost:6379".to_string());
+        let conn = bb8_redis::RedisConnectionManager::new(url)
+            .expect("Error while constructing Redis connection!");
+        let pool = bb8::Pool::builder().build_unchecked(conn);
+
+        Self { pool }
+    }
+
+    #[instrument(skip(self, session_hash), fields(elapsed), ret)]
+    pub async fn get_user_id(&self, session_hash: &str) -> AuthResult<i64> {
+        mi_db::get_user_id(session_hash, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self, session_hash), fields(elapsed), ret)]
+    pub async fn get_session_owner(&self, session_hash: &str) -> AuthResult<SessionOwner> {
+        mi_db::get_session_owner(session_hash, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self, session_hash, new_session_hash), fields(elapsed), ret)]
+    pub async fn rotate_session_token(
+        &self,
+        owner: &SessionOwner,
+        session_hash: &str,
+        new_session_hash: &str,
+    ) -> AuthResult<bool> {
+        mi_db::rotate_session_token(owner, session_hash, new_session_hash, &self.pool)
             .log_elapsed()
             .await
     }
@@ -46,9 +76,51 @@ impl RedisDb {
             .await
     }
 
-    #[instrument(skip(self, session_token), fields(elapsed), ret)]
-    pub async fn set_session_token(&self, user_id: i64, session_token: u128) -> AuthResult<()> {
-        mi_db::set_session_token(user_id, session_token, &self.pool)
+    #[instrument(skip(self), fields(elapsed))]
+    pub async fn get_app_access_token(&self) -> AuthResult<String> {
+        mi_db::get_app_access_token(&self.pool).log_elapsed().await
+    }
+
+    #[instrument(skip(self, access_token), fields(elapsed), ret)]
+    pub async fn set_app_access_token(
+        &self,
+        access_token: &str,
+        expires_in: usize,
+    ) -> AuthResult<()> {
+        mi_db::set_app_access_token(access_token, expires_in, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self, session_hash), fields(elapsed), ret)]
+    pub async fn set_session_token(
+        &self,
+        user_id: i64,
+        session_hash: &str,
+        session: &Session,
+    ) -> AuthResult<()> {
+        mi_db::set_session_token(user_id, session_hash, session, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed))]
+    pub async fn get_user_sessions(&self, user_id: i64) -> AuthResult<Vec<(String, Session)>> {
+        mi_db::get_user_sessions(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn delete_user_session(&self, user_id: i64, session_id: &str) -> AuthResult<bool> {
+        mi_db::delete_user_session(user_id, session_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn delete_user_sessions(&self, user_id: i64) -> AuthResult<()> {
+        mi_db::delete_user_sessions(user_id, &self.pool)
             .log_elapsed()
             .await
     }
@@ -59,14 +131,68 @@ impl RedisDb {
         user_id: i64,
         access_token: &str,
         refresh_token: &str,
+        expires_in: usize,
+    ) -> AuthResult<()> {
+        mi_db::set_osu_tokens(user_id, access_token, refresh_token, expires_in, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self, oauth_state), fields(elapsed), ret)]
+    pub async fn set_oauth_state(
+        &self,
+        oauth_state: &str,
+        return_to: Option<&str>,
     ) -> AuthResult<()> {
-        mi_db::set_osu_tokens(user_id, access_token, refresh_token, &self.pool)
+        mi_db::set_oauth_state(oauth_state, return_to, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self, oauth_state), fields(elapsed), ret)]
+    pub async fn take_oauth_state(&self, oauth_state: &str) -> AuthResult<Option<String>> {
+        mi_db::take_oauth_state(oauth_state, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn lock_osu_token_refresh(&self, user_id: i64) -> AuthResult<bool> {
+        mi_db::lock_osu_token_refresh(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn is_osu_token_refresh_locked(&self, user_id: i64) -> AuthResult<bool> {
+        mi_db::is_osu_token_refresh_locked(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn unlock_osu_token_refresh(&self, user_id: i64) -> AuthResult<()> {
+        mi_db::unlock_osu_token_refresh(user_id, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self, session_hash), fields(elapsed), ret)]
+    pub async fn delete_session_token(&self, session_hash: &str) -> AuthResult<()> {
+        mi_db::delete_session_token(session_hash, &self.pool)
+            .log_elapsed()
+            .await
+    }
+
+    #[instrument(skip(self), fields(elapsed), ret)]
+    pub async fn delete_osu_tokens(&self, user_id: i64) -> AuthResult<()> {
+        mi_db::delete_osu_tokens(user_id, &self.pool)
             .log_elapsed()
             .await
     }
 
     #[instrument(skip(self), fields(elapsed), ret)]
-    pub async fn lock_user(&self, user_id: i64) -> Result<(), LockError> {
+    pub async fn lock_user(&self, user_id: i64) -> Result<bool, LockError> {
         mi_db::lock_user(user_id, &self.pool).log_elapsed().await
     }
 
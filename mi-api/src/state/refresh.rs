@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use mi_db::{FullUser, MaxAge, StalenessRule};
+
+use super::SharedState;
+use crate::result::AppResult;
+
+const DEFAULT_MAX_AGE_MINUTES: i64 = 3 * 60;
+const DEFAULT_COOLDOWN_SECONDS: i64 = 60;
+
+/// Decides when the osu! data of a user is refreshed, both while their profile is requested and in
+/// the background.
+#[derive(Debug, Clone)]
+pub struct RefreshPolicy {
+    staleness_rule: Arc<dyn StalenessRule>,
+    /// Minimum time between two refreshes of the same user
+    cooldown: Duration,
+}
+
+impl RefreshPolicy {
+    pub fn new(staleness_rule: impl StalenessRule + 'static, cooldown: Duration) -> Self {
+        Self {
+            staleness_rule: Arc::new(staleness_rule),
+            cooldown,
+        }
+    }
+
+    /// Reads the policy from `MI_OSU_DATA_MAX_AGE_MINUTES` and
+    /// `MI_OSU_DATA_REFRESH_COOLDOWN_SECONDS`, falling back to the default policy for the ones that
+    /// are not set.
+    pub fn from_env() -> Self {
+        let max_age_minutes = match std::env::var("MI_OSU_DATA_MAX_AGE_MINUTES") {
+            Ok(minutes) => minutes
+                .parse()
+                .expect("Environment variable MI_OSU_DATA_MAX_AGE_MINUTES is not a number."),
+            Err(_) => DEFAULT_MAX_AGE_MINUTES,
+        };
+        let cooldown_seconds = match std::env::var("MI_OSU_DATA_REFRESH_COOLDOWN_SECONDS") {
+            Ok(seconds) => seconds.parse().expect(
+                "Environment variable MI_OSU_DATA_REFRESH_COOLDOWN_SECONDS is not a number.",
+            ),
+            Err(_) => DEFAULT_COOLDOWN_SECONDS,
+        };
+
+        Self::new(
+            MaxAge(Duration::minutes(max_age_minutes)),
+            Duration::seconds(cooldown_seconds),
+        )
+    }
+
+    /// Whether the osu! data of the user should be refreshed before it is returned.
+    pub fn is_stale(&self, user: &FullUser) -> bool {
+        user.is_outdated(self.staleness_rule.as_ref())
+    }
+
+    /// Osu! data that was last stored before the returned date is stale.
+    pub fn stale_before(&self) -> DateTime<Utc> {
+        self.staleness_rule.stale_before(Utc::now())
+    }
+
+    /// Whether the osu! data of the user was refreshed too recently to be refreshed again.
+    pub fn is_throttled(&self, user: &FullUser) -> bool {
+        Utc::now() - user.osu_data_modified_at < self.cooldown
+    }
+}
+
+impl SharedState {
+    /// Pulls the osu! data of a user and stores it, holding the user lock meanwhile.
+    ///
+    /// Returns `false` without doing anything if the user is already locked by another refresh.
+    pub(crate) async fn refresh_user_osu_data(
+        &self,
+        osu_token: &str,
+        user_id: i64,
+    ) -> AppResult<bool> {
+        if !self.redis().lock_user(user_id).await? {
+            return Ok(false);
+        }
+
+        let refresh_result: AppResult<()> = async {
+            let osu_user = self.http().request_osu_user(osu_token, user_id).await?;
+            self.postgres().update_user_osu_data(osu_user).await?;
+            Ok(())
+        }
+        .await;
+
+        // Unlock even if the refresh failed so the next attempt doesn't have to wait for the lock
+        // to expire
+        self.redis().unlock_user(user_id).await?;
+        refresh_result.map(|_| true)
+    }
+
+    /// Pulls every beatmapset of the given mapper from osu! and stores them on `user_osu_maps`.
+    /// Returns the number of stored beatmapsets.
+    pub(crate) async fn sync_user_mapsets(
+        &self,
+        osu_token: &str,
+        user_id: i64,
+    ) -> AppResult<usize> {
+        let mapsets = self.http().get_all_user_mapsets(user_id, osu_token).await?;
+        let mapset_count = mapsets.len();
+
+        self.postgres()
+            .upsert_user_mapsets(user_id, mapsets)
+            .await?;
+
+        Ok(mapset_count)
+    }
+}
+
+impl Default for RefreshPolicy {
+    fn default() -> Self {
+        Self::new(
+            MaxAge(Duration::minutes(DEFAULT_MAX_AGE_MINUTES)),
+            Duration::seconds(DEFAULT_COOLDOWN_SECONDS),
+        )
+    }
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_utils::init_user_for_test;
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_refresh_policy(db: PgPool) {
+        init_user_for_test(1, &db).await;
+        let mut user = mi_db::get_full_user(1, &db).await.unwrap();
+        let policy = RefreshPolicy::new(MaxAge(Duration::minutes(10)), Duration::seconds(60));
+
+        // Users are created with fresh osu! data
+        assert!(policy.is_throttled(&user));
+        assert!(!policy.is_stale(&user));
+
+        user.osu_data_modified_at = Utc::now() - Duration::minutes(5);
+        assert!(!policy.is_throttled(&user));
+        assert!(!policy.is_stale(&user));
+        assert!(user.osu_data_modified_at > policy.stale_before());
+
+        user.osu_data_modified_at = Utc::now() - Duration::minutes(15);
+        assert!(!policy.is_throttled(&user));
+        assert!(policy.is_stale(&user));
+        assert!(user.osu_data_modified_at < policy.stale_before());
+    }
+}
@@ -0,0 +1,130 @@
+//! Background tasks that run alongside the server.
+
+use std::time::Duration;
+
+use mi_core::AppErrorExt;
+use tracing::info;
+
+use crate::result::AppResult;
+use crate::state::SharedState;
+
+/// How often deleted influences that are past their restore window are removed.
+const INFLUENCE_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically removes the soft deleted influences that can no longer be restored.
+pub async fn purge_deleted_influences(state: SharedState) {
+    let mut interval = tokio::time::interval(INFLUENCE_PURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match state.postgres().purge_deleted_influences().await {
+            Ok(0) => {}
+            Ok(purged) => info!(purged, "Purged deleted influences"),
+            Err(err) => err.log_error(),
+        }
+    }
+}
+
+/// How often users with stale mapsets are looked for.
+const MAPSET_REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Maximum number of users whose mapsets are refreshed in a single run.
+const MAPSET_REFRESH_BATCH_SIZE: i64 = 20;
+/// Delay between users to stay well under osu! API rate limits. Each user takes 4 requests.
+const MAPSET_REFRESH_DELAY: Duration = Duration::from_secs(2);
+
+/// Periodically pulls the mapsets of the users whose stored mapsets are missing or stale.
+pub async fn refresh_stale_mapsets(state: SharedState) {
+    let mut interval = tokio::time::interval(MAPSET_REFRESH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = refresh_stale_mapsets_batch(&state).await {
+            Box::<dyn AppErrorExt>::from(err).log_error();
+        }
+    }
+}
+
+async fn refresh_stale_mapsets_batch(state: &SharedState) -> AppResult<()> {
+    let user_ids = state
+        .postgres()
+        .get_users_with_stale_mapsets(MAPSET_REFRESH_BATCH_SIZE)
+        .await?;
+    if user_ids.is_empty() {
+        return Ok(());
+    }
+
+    let osu_token = state.app_access_token().await?;
+
+    for user_id in user_ids {
+        // A single failing user should not stop the rest of the batch
+        match state.sync_user_mapsets(&osu_token, user_id).await {
+            Ok(mapset_count) => info!(user_id, mapset_count, "Refreshed user mapsets"),
+            Err(err) => Box::<dyn AppErrorExt>::from(err).log_error(),
+        }
+
+        tokio::time::sleep(MAPSET_REFRESH_DELAY).await;
+    }
+
+    Ok(())
+}
+
+/// How often users with stale osu! data are looked for.
+const OSU_DATA_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Maximum number of users whose osu! data is refreshed in a single run.
+const OSU_DATA_REFRESH_BATCH_SIZE: i64 = 50;
+/// Delay between users to stay well under osu! API rate limits. Each user takes a single request.
+const OSU_DATA_REFRESH_DELAY: Duration = Duration::from_secs(1);
+
+/// Periodically refreshes the osu! data of the users whose stored data is stale according to the
+/// [`RefreshPolicy`] of the state.
+///
+/// [`RefreshPolicy`]: crate::state::RefreshPolicy
+pub async fn refresh_stale_osu_data(state: SharedState) {
+    let mut interval = tokio::time::interval(OSU_DATA_REFRESH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = refresh_stale_osu_data_batch(&state).await {
+            Box::<dyn AppErrorExt>::from(err).log_error();
+        }
+    }
+}
+
+async fn refresh_stale_osu_data_batch(state: &SharedState) -> AppResult<()> {
+    let stale_before = state.refresh_policy().stale_before();
+    let user_ids = state
+        .postgres()
+        .get_users_with_stale_osu_data(stale_before, OSU_DATA_REFRESH_BATCH_SIZE)
+        .await?;
+    if user_ids.is_empty() {
+        return Ok(());
+    }
+
+    let osu_token = state.app_access_token().await?;
+
+    for user_id in user_ids {
+        match state.refresh_user_osu_data(&osu_token, user_id).await {
+            Ok(true) => info!(user_id, "Refreshed user osu! data"),
+            // Someone else is already refreshing the user
+            Ok(false) => continue,
+            Err(err) => {
+                Box::<dyn AppErrorExt>::from(err).log_error();
+                // Otherwise the user would be picked first on every run
+                if let Err(err) = state
+                    .postgres()
+                    .record_osu_data_refresh_attempt(user_id)
+                    .await
+                {
+                    err.log_error();
+                }
+            }
+        }
+
+        tokio::time::sleep(OSU_DATA_REFRESH_DELAY).await;
+    }
+
+    Ok(())
+}
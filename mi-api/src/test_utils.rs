@@ -0,0 +1,21 @@
+use mi_db::{PrivacySettings, User};
+use sqlx::PgPool;
+
+pub(crate) async fn init_user_for_test(user_id: i64, db: &PgPool) {
+    let user = User::new(
+        user_id,
+        format!("mapper{user_id}"),
+        format!("random.imageservice.com/mapper{user_id}.jpg"),
+    );
+    mi_db::init_user(user, db).await.unwrap();
+}
+
+pub(crate) async fn hide_profile_for_test(user_id: i64, db: &PgPool) {
+    let settings = PrivacySettings {
+        hide_profile: true,
+        ..Default::default()
+    };
+    mi_db::update_privacy_settings(user_id, settings, db)
+        .await
+        .unwrap();
+}
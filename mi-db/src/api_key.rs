@@ -0,0 +1,297 @@
+use chrono::Utc;
+use mi_core::error::{AppErrorExt, ErrorType};
+use mi_core::INTERNAL_DB_ERROR_MESSAGE;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgHasArrayType, PgTypeInfo};
+use sqlx::{FromRow, PgPool};
+use thiserror::Error;
+use tracing::{error, warn};
+use utoipa::ToSchema;
+
+/// Maximum number of API keys a user can have at once.
+pub const API_KEY_LIMIT: i64 = 10;
+
+/// What an API key is allowed to do on behalf of its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[sqlx(type_name = "api_key_scope", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    /// Allows requests that only read data
+    Read,
+    /// Allows requests that change data
+    Write,
+}
+
+impl PgHasArrayType for ApiKeyScope {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_api_key_scope")
+    }
+}
+
+/// A personal API key. The key itself is never stored, only its hash.
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiKey {
+    pub id: i64,
+    /// Id of the user that owns the key
+    pub user_id: i64,
+    /// Name the user gave to the key
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    /// Creation date of the key
+    pub created_at: chrono::DateTime<Utc>,
+    /// Date of the last request made with the key
+    pub last_used_at: Option<chrono::DateTime<Utc>>,
+    /// Expiry date of the key. Null if the key does not expire
+    pub expires_at: Option<chrono::DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+/// Stores a new API key of a user.
+///
+/// Fails with [`ApiKeyError::UserNotFound`] if the user does not exist and with
+/// [`ApiKeyError::TooManyApiKeys`] if the user already has [`API_KEY_LIMIT`] keys that are not
+/// expired.
+pub async fn insert_api_key(
+    user_id: i64,
+    name: &str,
+    key_hash: &str,
+    scopes: &[ApiKeyScope],
+    expires_at: Option<chrono::DateTime<Utc>>,
+    db: &PgPool,
+) -> Result<ApiKey, ApiKeyError> {
+    let mut transaction = db.begin().await?;
+
+    // Locks the user so concurrent inserts can't go over the limit.
+    let user_exists = sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+        .fetch_optional(&mut transaction)
+        .await?
+        .is_some();
+
+    if !user_exists {
+        return Err(ApiKeyError::UserNotFound(user_id));
+    }
+
+    let key_count = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM api_keys
+        WHERE user_id = $1 AND (expires_at IS NULL OR expires_at > NOW())"#,
+        user_id
+    )
+    .fetch_one(&mut transaction)
+    .await?
+    .count;
+
+    if key_count >= API_KEY_LIMIT {
+        return Err(ApiKeyError::TooManyApiKeys);
+    }
+
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        r#"
+        INSERT INTO api_keys (user_id, name, key_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING
+            id, user_id, name, scopes as "scopes: Vec<ApiKeyScope>", created_at, last_used_at,
+            expires_at"#,
+        user_id,
+        name,
+        key_hash,
+        scopes as _,
+        expires_at
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(api_key)
+}
+
+/// Returns the API keys of a user, newest first. Expired keys are included.
+pub async fn get_user_api_keys(user_id: i64, db: &PgPool) -> Result<Vec<ApiKey>, ApiKeyError> {
+    let api_keys = sqlx::query_as!(
+        ApiKey,
+        r#"
+        SELECT
+            id, user_id, name, scopes as "scopes: Vec<ApiKeyScope>", created_at, last_used_at,
+            expires_at
+        FROM api_keys
+        WHERE user_id = $1
+        ORDER BY created_at DESC, id DESC"#,
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(api_keys)
+}
+
+/// Returns the API key with the given hash and marks it as used.
+///
+/// Fails with [`ApiKeyError::InvalidApiKey`] if there is no such key or the key is expired.
+pub async fn authenticate_api_key(key_hash: &str, db: &PgPool) -> Result<ApiKey, ApiKeyError> {
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        r#"
+        UPDATE api_keys SET last_used_at = NOW()
+        WHERE key_hash = $1 AND (expires_at IS NULL OR expires_at > NOW())
+        RETURNING
+            id, user_id, name, scopes as "scopes: Vec<ApiKeyScope>", created_at, last_used_at,
+            expires_at"#,
+        key_hash
+    )
+    .fetch_optional(db)
+    .await?;
+
+    api_key.ok_or(ApiKeyError::InvalidApiKey)
+}
+
+/// Revokes an API key of a user.
+pub async fn delete_api_key(user_id: i64, key_id: i64, db: &PgPool) -> Result<(), ApiKeyError> {
+    let query_result = sqlx::query!(
+        "DELETE FROM api_keys WHERE id = $1 AND user_id = $2",
+        key_id,
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    if query_result.rows_affected() == 0 {
+        return Err(ApiKeyError::ApiKeyNotFound(key_id));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Error)]
+pub enum ApiKeyError {
+    #[error("API key with id `{0}` is not found.")]
+    ApiKeyNotFound(i64),
+    #[error("API key is invalid or expired.")]
+    InvalidApiKey,
+    #[error("API key does not have the `{0:?}` scope.")]
+    MissingScope(ApiKeyScope),
+    #[error("A user can have at most {} API keys.", API_KEY_LIMIT)]
+    TooManyApiKeys,
+    #[error("User with id `{0}` is not found.")]
+    UserNotFound(i64),
+    #[error("Internal database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl AppErrorExt for ApiKeyError {
+    fn user_message(&self) -> String {
+        match self {
+            ApiKeyError::ApiKeyNotFound(_) => self.to_string(),
+            ApiKeyError::InvalidApiKey => self.to_string(),
+            ApiKeyError::MissingScope(_) => self.to_string(),
+            ApiKeyError::TooManyApiKeys => self.to_string(),
+            ApiKeyError::UserNotFound(_) => self.to_string(),
+            ApiKeyError::DatabaseError(_) => INTERNAL_DB_ERROR_MESSAGE.to_string(),
+        }
+    }
+
+    fn error_type(&self) -> ErrorType {
+        match self {
+            ApiKeyError::ApiKeyNotFound(_) => ErrorType::DataNotFound,
+            ApiKeyError::InvalidApiKey => ErrorType::AuthorizatonError,
+            ApiKeyError::MissingScope(_) => ErrorType::AuthorizatonError,
+            ApiKeyError::TooManyApiKeys => ErrorType::BadRequestData,
+            ApiKeyError::UserNotFound(_) => ErrorType::DataNotFound,
+            ApiKeyError::DatabaseError(_) => ErrorType::DatabaseError,
+        }
+    }
+
+    fn log_error(&self) {
+        match self {
+            ApiKeyError::ApiKeyNotFound(_) => warn!("{}", self.to_string()),
+            ApiKeyError::InvalidApiKey => warn!("{}", self.to_string()),
+            ApiKeyError::MissingScope(_) => warn!("{}", self.to_string()),
+            ApiKeyError::TooManyApiKeys => warn!("{}", self.to_string()),
+            ApiKeyError::UserNotFound(_) => warn!("{}", self.to_string()),
+            ApiKeyError::DatabaseError(_) => error!("{}", self.to_string()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use chrono::Duration;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::user::{init_user, User};
+
+    fn user_for_test(user_id: i64) -> User {
+        User::new(
+            user_id,
+            "boraarslan".to_string(),
+            "random.imageservice.com/boraarslan.jpg".to_string(),
+        )
+    }
+
+    #[sqlx::test]
+    async fn test_api_keys(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+
+        let api_key = insert_api_key(1, "bot", "hash1", &[ApiKeyScope::Read], None, &db)
+            .await
+            .unwrap();
+        assert!(api_key.has_scope(ApiKeyScope::Read));
+        assert!(!api_key.has_scope(ApiKeyScope::Write));
+        insert_api_key(
+            1,
+            "expired",
+            "hash2",
+            &[ApiKeyScope::Read, ApiKeyScope::Write],
+            Some(Utc::now() - Duration::hours(1)),
+            &db,
+        )
+        .await
+        .unwrap();
+
+        let authenticated = authenticate_api_key("hash1", &db).await.unwrap();
+        assert_eq!(authenticated.id, api_key.id);
+        assert!(authenticated.last_used_at.is_some());
+
+        let err = authenticate_api_key("hash2", &db).await.unwrap_err();
+        assert!(matches!(err, ApiKeyError::InvalidApiKey));
+        let err = authenticate_api_key("hash3", &db).await.unwrap_err();
+        assert!(matches!(err, ApiKeyError::InvalidApiKey));
+
+        assert_eq!(get_user_api_keys(1, &db).await.unwrap().len(), 2);
+
+        let err = delete_api_key(2, api_key.id, &db).await.unwrap_err();
+        assert!(matches!(err, ApiKeyError::ApiKeyNotFound(_)));
+        delete_api_key(1, api_key.id, &db).await.unwrap();
+        let err = authenticate_api_key("hash1", &db).await.unwrap_err();
+        assert!(matches!(err, ApiKeyError::InvalidApiKey));
+
+        for i in 0..API_KEY_LIMIT {
+            insert_api_key(
+                2,
+                "bot",
+                &format!("hash2{}", i),
+                &[ApiKeyScope::Read],
+                None,
+                &db,
+            )
+            .await
+            .unwrap();
+        }
+        let err = insert_api_key(2, "bot", "hash", &[ApiKeyScope::Read], None, &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiKeyError::TooManyApiKeys));
+
+        let err = insert_api_key(3, "bot", "hash", &[ApiKeyScope::Read], None, &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiKeyError::UserNotFound(3)));
+    }
+}
@@ -1,34 +1,110 @@
+use std::collections::HashMap;
+
 use bb8::RunError;
+use chrono::Utc;
 use mi_core::error::{AppErrorExt, ErrorType};
 use mi_core::INTERNAL_DB_ERROR_MESSAGE;
 use redis::RedisError;
 use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::{error, warn};
+use utoipa::ToSchema;
 
 use crate::RedisPool;
 
 pub type AuthResult<T> = Result<T, AuthError>;
 
-const SESSION_TOKEN_TIMEOUT: usize = 85800; // 23 hours 50 minutes
-const ACCESS_TOKEN_TIMEOUT: usize = 43200; // 12 hours
+/// Seconds a session token stays valid. Extended whenever the token is rotated.
+pub const SESSION_TOKEN_TIMEOUT: usize = 85800; // 23 hours 50 minutes
+/// Seconds a rotated session token keeps working.
+pub const ROTATED_SESSION_TOKEN_GRACE_PERIOD: usize = 60;
+const ACCESS_TOKEN_EXPIRY_MARGIN: usize = 300; // 5 minutes
+const OSU_TOKEN_REFRESH_LOCK_TIMEOUT: usize = 10000; // 10 seconds, in milliseconds
+const OAUTH_STATE_TIMEOUT: usize = 600; // 10 minutes
+const APP_ACCESS_TOKEN_KEY: &str = "app:access";
+
+/// A login of a user. Listed to the user so they can revoke the ones they don't recognize.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Session {
+    /// Id of the session. Unlike the session token, it is safe to be shown
+    pub id: String,
+    /// Date of the login
+    pub created_at: chrono::DateTime<Utc>,
+    /// User agent of the client that logged in
+    pub user_agent: Option<String>,
+}
 
-pub async fn get_user_id(session_token: u128, db: &RedisPool) -> AuthResult<i64> {
-    let mut conn = db.get().await?;
-    let mut cmd = redis::Cmd::new();
-    let key = format!("user:session:{}", session_token);
-    cmd.arg("GET").arg(key);
-    let token: Option<i64> = cmd.query_async(&mut *conn).await?;
+/// A [`Session`] as it is stored on `user:sessions:{user_id}`, keyed by its id.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredSession {
+    /// Hash of the current token of the session
+    session_hash: String,
+    /// Hash of the token replaced on the last rotation, which might still be in its grace period
+    #[serde(default)]
+    previous_session_hash: Option<String>,
+    #[serde(flatten)]
+    session: Session,
+}
 
-    match token {
-        Some(token) => Ok(token),
-        None => Err(AuthError::ValueNotFound {
-            value: Secret::new(session_token.to_string()),
-            expected: "user_id",
-        }),
+impl StoredSession {
+    /// Keys of the tokens of the session that might still be alive.
+    fn token_keys(&self) -> impl Iterator<Item = String> + '_ {
+        std::iter::once(&self.session_hash)
+            .chain(self.previous_session_hash.as_ref())
+            .map(|session_hash| session_key(session_hash))
     }
 }
 
+/// Owner of a session token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionOwner {
+    pub user_id: i64,
+    /// Id of the [`Session`] the token belongs to
+    pub session_id: String,
+    /// Seconds left until the token expires
+    pub expires_in: usize,
+}
+
+/// Session tokens are only stored as keyed hashes, so a copy of the database doesn't leak any
+/// live sessions.
+fn session_key(session_hash: &str) -> String {
+    format!("user:session:{}", session_hash)
+}
+
+/// Returns the owner of a session token, looked up with the hash of the token.
+pub async fn get_session_owner(session_hash: &str, db: &RedisPool) -> AuthResult<SessionOwner> {
+    let mut conn = db.get().await?;
+    let key = session_key(session_hash);
+
+    let (value, ttl): (Option<String>, i64) = redis::pipe()
+        .cmd("GET")
+        .arg(&key)
+        .cmd("TTL")
+        .arg(&key)
+        .query_async(&mut *conn)
+        .await?;
+
+    // Values are stored as `{user_id}:{session_id}`
+    let owner = value.as_deref().and_then(|value| {
+        let (user_id, session_id) = value.split_once(':')?;
+        Some(SessionOwner {
+            user_id: user_id.parse().ok()?,
+            session_id: session_id.to_string(),
+            expires_in: ttl.max(0) as usize,
+        })
+    });
+
+    owner.ok_or_else(|| AuthError::ValueNotFound {
+        value: Secret::new(session_hash.to_string()),
+        expected: "user_id",
+    })
+}
+
+pub async fn get_user_id(session_hash: &str, db: &RedisPool) -> AuthResult<i64> {
+    Ok(get_session_owner(session_hash, db).await?.user_id)
+}
+
 pub async fn get_access_token(user_id: i64, db: &RedisPool) -> AuthResult<String> {
     let mut conn = db.get().await?;
     let mut cmd = redis::Cmd::new();
@@ -63,38 +139,269 @@ pub async fn get_refresh_token(user_id: i64, db: &RedisPool) -> AuthResult<Strin
     }
 }
 
+/// Stores a session token and adds the session to the active sessions of the user.
 pub async fn set_session_token(
     user_id: i64,
-    session_token: u128,
+    session_hash: &str,
+    session: &Session,
     db: &RedisPool,
 ) -> AuthResult<()> {
     let mut conn = db.get().await?;
-    let mut cmd = redis::Cmd::new();
-    let key = format!("user:session:{}", session_token);
+    let mut pipe = redis::pipe();
+    let sessions_key = format!("user:sessions:{}", user_id);
+    let stored_session = serde_json::to_string(&StoredSession {
+        session_hash: session_hash.to_string(),
+        previous_session_hash: None,
+        session: session.clone(),
+    })?;
+
+    pipe.atomic();
+    pipe.cmd("SET")
+        .arg(session_key(session_hash))
+        .arg(format!("{}:{}", user_id, session.id))
+        .arg("EX")
+        .arg(SESSION_TOKEN_TIMEOUT)
+        .ignore();
+    pipe.cmd("HSET")
+        .arg(&sessions_key)
+        .arg(&session.id)
+        .arg(stored_session)
+        .ignore();
+    // The set outlives every session in it since it is extended with the newest one.
+    pipe.cmd("EXPIRE")
+        .arg(&sessions_key)
+        .arg(SESSION_TOKEN_TIMEOUT)
+        .ignore();
+    pipe.query_async(&mut *conn).await?;
+
+    Ok(())
+}
+
+/// Replaces the token of a session with a new one that expires [`SESSION_TOKEN_TIMEOUT`] seconds
+/// later. Returns `false` if the token is not rotated.
+///
+/// The old token keeps working for [`ROTATED_SESSION_TOKEN_GRACE_PERIOD`] seconds so requests
+/// that were already sent with it don't fail. Only the current token of a session can be rotated,
+/// and only once, so concurrent requests with the same token don't fork the session.
+pub async fn rotate_session_token(
+    owner: &SessionOwner,
+    session_hash: &str,
+    new_session_hash: &str,
+    db: &RedisPool,
+) -> AuthResult<bool> {
+    let mut conn = db.get().await?;
+    let rotation_key = format!("user:session_rotation:{}", session_hash);
+
+    let rotation_lock: Option<String> = redis::cmd("SET")
+        .arg(&rotation_key)
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(ROTATED_SESSION_TOKEN_GRACE_PERIOD)
+        .query_async(&mut *conn)
+        .await?;
+    if rotation_lock.is_none() {
+        return Ok(false);
+    }
+
+    // Revoking the session deletes its token, so the transaction below is discarded if the session
+    // is revoked while it is being rotated. Other sessions of the user don't interfere with it.
+    redis::cmd("WATCH")
+        .arg(session_key(session_hash))
+        .query_async(&mut *conn)
+        .await?;
+
+    let rotated =
+        replace_watched_session_token(owner, session_hash, new_session_hash, &mut *conn).await;
+    if !matches!(rotated, Ok(true)) {
+        // Connections go back to the pool, they can't keep watching the token
+        redis::cmd("UNWATCH").query_async(&mut *conn).await?;
+    }
+
+    rotated
+}
+
+/// Stores the new token of the session in a transaction. Expects the old token to be watched.
+async fn replace_watched_session_token(
+    owner: &SessionOwner,
+    session_hash: &str,
+    new_session_hash: &str,
+    conn: &mut (impl redis::aio::ConnectionLike + Send),
+) -> AuthResult<bool> {
+    let sessions_key = format!("user:sessions:{}", owner.user_id);
+
+    let stored_session: Option<String> = redis::cmd("HGET")
+        .arg(&sessions_key)
+        .arg(&owner.session_id)
+        .query_async(conn)
+        .await?;
+    let Some(stored_session) = stored_session else {
+        return Ok(false);
+    };
+    let stored_session: StoredSession = serde_json::from_str(&stored_session)?;
+    if stored_session.session_hash != session_hash {
+        return Ok(false);
+    }
+
+    let stored_session = serde_json::to_string(&StoredSession {
+        session_hash: new_session_hash.to_string(),
+        previous_session_hash: Some(session_hash.to_string()),
+        session: stored_session.session,
+    })?;
+
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    pipe.cmd("SET")
+        .arg(session_key(new_session_hash))
+        .arg(format!("{}:{}", owner.user_id, owner.session_id))
+        .arg("EX")
+        .arg(SESSION_TOKEN_TIMEOUT)
+        .ignore();
+    pipe.cmd("EXPIRE")
+        .arg(session_key(session_hash))
+        .arg(ROTATED_SESSION_TOKEN_GRACE_PERIOD)
+        .ignore();
+    pipe.cmd("HSET")
+        .arg(&sessions_key)
+        .arg(&owner.session_id)
+        .arg(stored_session)
+        .ignore();
+    pipe.cmd("EXPIRE")
+        .arg(&sessions_key)
+        .arg(SESSION_TOKEN_TIMEOUT)
+        .ignore();
+    // Nil if the transaction is discarded
+    let committed: Option<()> = pipe.query_async(conn).await?;
+
+    Ok(committed.is_some())
+}
 
-    cmd.arg("SET").arg(&key).arg(user_id);
-    cmd.arg("EX").arg(SESSION_TOKEN_TIMEOUT);
+/// Returns the active sessions of a user with the hashes of their tokens, newest first.
+///
+/// Sessions whose tokens are expired are removed from the set of the user along the way.
+pub async fn get_user_sessions(user_id: i64, db: &RedisPool) -> AuthResult<Vec<(String, Session)>> {
+    let mut conn = db.get().await?;
+    let sessions_key = format!("user:sessions:{}", user_id);
+
+    let stored_sessions: HashMap<String, String> = redis::cmd("HGETALL")
+        .arg(&sessions_key)
+        .query_async(&mut *conn)
+        .await?;
+    let stored_sessions = stored_sessions
+        .values()
+        .map(|stored_session| serde_json::from_str::<StoredSession>(stored_session))
+        .collect::<Result<Vec<_>, _>>()?;
+    if stored_sessions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut pipe = redis::pipe();
+    for stored_session in &stored_sessions {
+        pipe.cmd("EXISTS")
+            .arg(session_key(&stored_session.session_hash));
+    }
+    let exists: Vec<bool> = pipe.query_async(&mut *conn).await?;
+
+    let mut sessions = Vec::with_capacity(stored_sessions.len());
+    let mut expired_ids = Vec::new();
+    for (stored_session, exists) in stored_sessions.into_iter().zip(exists) {
+        if exists {
+            sessions.push((stored_session.session_hash, stored_session.session));
+        } else {
+            expired_ids.push(stored_session.session.id);
+        }
+    }
+
+    if !expired_ids.is_empty() {
+        redis::cmd("HDEL")
+            .arg(&sessions_key)
+            .arg(expired_ids)
+            .query_async(&mut *conn)
+            .await?;
+    }
+
+    sessions.sort_by(|(_, a), (_, b)| b.created_at.cmp(&a.created_at));
+    Ok(sessions)
+}
+
+/// Revokes a session of a user by its id. Returns `false` if the user has no such session.
+pub async fn delete_user_session(
+    user_id: i64,
+    session_id: &str,
+    db: &RedisPool,
+) -> AuthResult<bool> {
+    let mut conn = db.get().await?;
+    let sessions_key = format!("user:sessions:{}", user_id);
+
+    let stored_session: Option<String> = redis::cmd("HGET")
+        .arg(&sessions_key)
+        .arg(session_id)
+        .query_async(&mut *conn)
+        .await?;
+    let Some(stored_session) = stored_session else {
+        return Ok(false);
+    };
+    let stored_session: StoredSession = serde_json::from_str(&stored_session)?;
+
+    redis::pipe()
+        .atomic()
+        .cmd("DEL")
+        .arg(stored_session.token_keys().collect::<Vec<_>>())
+        .ignore()
+        .cmd("HDEL")
+        .arg(&sessions_key)
+        .arg(session_id)
+        .ignore()
+        .query_async(&mut *conn)
+        .await?;
+
+    Ok(true)
+}
+
+/// Revokes every session of a user.
+pub async fn delete_user_sessions(user_id: i64, db: &RedisPool) -> AuthResult<()> {
+    let mut conn = db.get().await?;
+    let sessions_key = format!("user:sessions:{}", user_id);
+
+    let stored_sessions: Vec<String> = redis::cmd("HVALS")
+        .arg(&sessions_key)
+        .query_async(&mut *conn)
+        .await?;
+
+    let mut cmd = redis::Cmd::new();
+    cmd.arg("DEL").arg(&sessions_key);
+    for stored_session in stored_sessions {
+        let stored_session: StoredSession = serde_json::from_str(&stored_session)?;
+        cmd.arg(stored_session.token_keys().collect::<Vec<_>>());
+    }
     cmd.query_async(&mut *conn).await?;
 
     Ok(())
 }
 
+/// Stores both osu! tokens of a user in a single transaction so they are always rotated together.
+///
+/// The access token is expired a few minutes before `expires_in` so that it gets refreshed before
+/// osu! starts rejecting it.
 pub async fn set_osu_tokens(
     user_id: i64,
     access_token: &str,
     refresh_token: &str,
+    expires_in: usize,
     db: &RedisPool,
 ) -> AuthResult<()> {
     let mut conn = db.get().await?;
     let mut pipe = redis::pipe();
     let access_key = format!("user:access:{}", user_id);
     let refresh_key = format!("user:refresh:{}", user_id);
+    let access_timeout = expires_in.saturating_sub(ACCESS_TOKEN_EXPIRY_MARGIN).max(1);
 
+    pipe.atomic();
     pipe.cmd("SET")
         .arg(&access_key)
         .arg(access_token)
         .arg("EX")
-        .arg(ACCESS_TOKEN_TIMEOUT)
+        .arg(access_timeout)
         .ignore();
 
     pipe.cmd("SET")
@@ -107,6 +414,167 @@ pub async fn set_osu_tokens(
     Ok(())
 }
 
+/// Removes a session token and its session from the active sessions of the user.
+///
+/// The token is deleted even if no owner can be read from it, so a broken token can't outlive a
+/// logout.
+pub async fn delete_session_token(session_hash: &str, db: &RedisPool) -> AuthResult<()> {
+    let owner = match get_session_owner(session_hash, db).await {
+        Ok(owner) => Some(owner),
+        Err(AuthError::ValueNotFound { .. }) => None,
+        Err(err) => return Err(err),
+    };
+
+    {
+        let mut conn = db.get().await?;
+        redis::cmd("DEL")
+            .arg(session_key(session_hash))
+            .query_async(&mut *conn)
+            .await?;
+    }
+
+    if let Some(owner) = owner {
+        delete_user_session(owner.user_id, &owner.session_id, db).await?;
+    }
+
+    Ok(())
+}
+
+/// Takes the lock that guards the osu! token refresh of a user. Returns `false` if another
+/// refresh holds it.
+///
+/// osu! invalidates a refresh token once it is used, so only one refresh can succeed at a time.
+pub async fn lock_osu_token_refresh(user_id: i64, db: &RedisPool) -> AuthResult<bool> {
+    let mut conn = db.get().await?;
+    let mut cmd = redis::Cmd::new();
+    let key = format!("user:refresh_lock:{}", user_id);
+
+    cmd.arg("SET")
+        .arg(&key)
+        .arg(1)
+        .arg("NX")
+        .arg("PX")
+        .arg(OSU_TOKEN_REFRESH_LOCK_TIMEOUT);
+    let reply: Option<String> = cmd.query_async(&mut *conn).await?;
+
+    Ok(reply.is_some())
+}
+
+/// Whether a refresh of the osu! tokens of a user holds the refresh lock.
+pub async fn is_osu_token_refresh_locked(user_id: i64, db: &RedisPool) -> AuthResult<bool> {
+    let mut conn = db.get().await?;
+    let mut cmd = redis::Cmd::new();
+    let key = format!("user:refresh_lock:{}", user_id);
+
+    cmd.arg("EXISTS").arg(&key);
+    let exists: bool = cmd.query_async(&mut *conn).await?;
+
+    Ok(exists)
+}
+
+pub async fn unlock_osu_token_refresh(user_id: i64, db: &RedisPool) -> AuthResult<()> {
+    let mut conn = db.get().await?;
+    let mut cmd = redis::Cmd::new();
+    let key = format!("user:refresh_lock:{}", user_id);
+
+    cmd.arg("DEL").arg(&key);
+    cmd.query_async(&mut *conn).await?;
+
+    Ok(())
+}
+
+/// Removes the osu! access and refresh tokens of a user.
+pub async fn delete_osu_tokens(user_id: i64, db: &RedisPool) -> AuthResult<()> {
+    let mut conn = db.get().await?;
+    let mut cmd = redis::Cmd::new();
+    let access_key = format!("user:access:{}", user_id);
+    let refresh_key = format!("user:refresh:{}", user_id);
+
+    cmd.arg("DEL").arg(access_key).arg(refresh_key);
+    cmd.query_async(&mut *conn).await?;
+
+    Ok(())
+}
+
+/// Stores the `state` of a started osu! login along with the path to return to after it.
+pub async fn set_oauth_state(
+    oauth_state: &str,
+    return_to: Option<&str>,
+    db: &RedisPool,
+) -> AuthResult<()> {
+    let mut conn = db.get().await?;
+    let mut cmd = redis::Cmd::new();
+    let key = format!("oauth:state:{}", oauth_state);
+
+    // An empty value means there is no path to return to
+    cmd.arg("SET").arg(&key).arg(return_to.unwrap_or_default());
+    cmd.arg("EX").arg(OAUTH_STATE_TIMEOUT);
+    cmd.query_async(&mut *conn).await?;
+
+    Ok(())
+}
+
+/// Removes the `state` of a started osu! login and returns the path to return to after it.
+///
+/// A state can only be taken once, fails with [`AuthError::ValueNotFound`] if it is already taken
+/// or expired.
+pub async fn take_oauth_state(oauth_state: &str, db: &RedisPool) -> AuthResult<Option<String>> {
+    let mut conn = db.get().await?;
+    let key = format!("oauth:state:{}", oauth_state);
+
+    let (return_to,): (Option<String>,) = redis::pipe()
+        .atomic()
+        .cmd("GET")
+        .arg(&key)
+        .cmd("DEL")
+        .arg(&key)
+        .ignore()
+        .query_async(&mut *conn)
+        .await?;
+
+    match return_to {
+        Some(return_to) if return_to.is_empty() => Ok(None),
+        Some(return_to) => Ok(Some(return_to)),
+        None => Err(AuthError::ValueNotFound {
+            value: Secret::new(oauth_state.to_string()),
+            expected: "oauth_state",
+        }),
+    }
+}
+
+/// Returns the cached client credentials token of the application.
+pub async fn get_app_access_token(db: &RedisPool) -> AuthResult<String> {
+    let mut conn = db.get().await?;
+    let mut cmd = redis::Cmd::new();
+
+    cmd.arg("GET").arg(APP_ACCESS_TOKEN_KEY);
+    let token: Option<String> = cmd.query_async(&mut *conn).await?;
+
+    match token {
+        Some(token) => Ok(token),
+        None => Err(AuthError::ValueNotFound {
+            value: Secret::new(APP_ACCESS_TOKEN_KEY.to_string()),
+            expected: "app_access_token",
+        }),
+    }
+}
+
+/// Caches the client credentials token of the application until it expires.
+pub async fn set_app_access_token(
+    access_token: &str,
+    expires_in: usize,
+    db: &RedisPool,
+) -> AuthResult<()> {
+    let mut conn = db.get().await?;
+    let mut cmd = redis::Cmd::new();
+
+    cmd.arg("SET").arg(APP_ACCESS_TOKEN_KEY).arg(access_token);
+    cmd.arg("EX").arg(expires_in);
+    cmd.query_async(&mut *conn).await?;
+
+    Ok(())
+}
+
 // TODO: Add record fields to errors.
 #[derive(Debug, Error)]
 pub enum AuthError {
@@ -119,6 +587,8 @@ pub enum AuthError {
         value: Secret<String>,
         expected: &'static str,
     },
+    #[error("Stored session could not be (de)serialized {0}")]
+    SessionSerdeError(#[from] serde_json::Error),
 }
 
 impl AppErrorExt for AuthError {
@@ -127,6 +597,7 @@ impl AppErrorExt for AuthError {
             AuthError::ValueNotFound { .. } => "Couldn't authorize the user".to_string(),
             AuthError::ConnectionTimedOut => INTERNAL_DB_ERROR_MESSAGE.to_string(),
             AuthError::RedisError(_) => INTERNAL_DB_ERROR_MESSAGE.to_string(),
+            AuthError::SessionSerdeError(_) => INTERNAL_DB_ERROR_MESSAGE.to_string(),
         }
     }
 
@@ -150,6 +621,9 @@ impl AppErrorExt for AuthError {
                 warn!("Getting a connection from Redis pool took too long.")
             }
             AuthError::RedisError(err) => error!("Redis database returned an error: {}", err),
+            AuthError::SessionSerdeError(err) => {
+                error!("Stored session could not be (de)serialized: {}", err)
+            }
         }
     }
 
@@ -158,6 +632,7 @@ impl AppErrorExt for AuthError {
             AuthError::ValueNotFound { .. } => ErrorType::AuthorizatonError,
             AuthError::ConnectionTimedOut => ErrorType::DatabaseError,
             AuthError::RedisError(_) => ErrorType::DatabaseError,
+            AuthError::SessionSerdeError(_) => ErrorType::DatabaseError,
         }
     }
 }
@@ -187,17 +662,167 @@ mod test {
             .unwrap()
     }
 
+    fn session_for_test(id: &str) -> Session {
+        Session {
+            id: id.to_string(),
+            created_at: Utc::now(),
+            user_agent: Some("Mozilla/5.0".to_string()),
+        }
+    }
+
     #[tokio::test]
     async fn test_session_token() {
         let user_id = 1;
-        let session_token = 12345;
+        let session_hash = "12345";
         let db_pool = create_db_pool().await;
 
-        set_session_token(user_id, session_token, &db_pool)
+        set_session_token(user_id, session_hash, &session_for_test("1"), &db_pool)
             .await
             .unwrap();
-        let db_user_id = get_user_id(session_token, &db_pool).await.unwrap();
+        let db_user_id = get_user_id(session_hash, &db_pool).await.unwrap();
         assert_eq!(user_id, db_user_id);
+
+        delete_session_token(session_hash, &db_pool).await.unwrap();
+        let err = get_user_id(session_hash, &db_pool).await.unwrap_err();
+        assert!(matches!(err, AuthError::ValueNotFound { .. }));
+        assert!(get_user_sessions(user_id, &db_pool)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_session_token_without_owner() {
+        let session_hash = "12346";
+        let db_pool = create_db_pool().await;
+
+        // A token whose value doesn't name an owner
+        {
+            let mut conn = db_pool.get().await.unwrap();
+            redis::cmd("SET")
+                .arg(session_key(session_hash))
+                .arg("not an owner")
+                .query_async::<_, ()>(&mut *conn)
+                .await
+                .unwrap();
+        }
+
+        delete_session_token(session_hash, &db_pool).await.unwrap();
+
+        let mut conn = db_pool.get().await.unwrap();
+        let exists: bool = redis::cmd("EXISTS")
+            .arg(session_key(session_hash))
+            .query_async(&mut *conn)
+            .await
+            .unwrap();
+        assert!(!exists);
+    }
+
+    #[tokio::test]
+    async fn test_user_sessions() {
+        let user_id = 2;
+        let db_pool = create_db_pool().await;
+
+        for (session_hash, session_id) in [("22345", "1"), ("22346", "2"), ("22347", "3")] {
+            set_session_token(
+                user_id,
+                session_hash,
+                &session_for_test(session_id),
+                &db_pool,
+            )
+            .await
+            .unwrap();
+        }
+
+        let sessions = get_user_sessions(user_id, &db_pool).await.unwrap();
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions[0].1.id, "3");
+
+        assert!(delete_user_session(user_id, "2", &db_pool).await.unwrap());
+        assert!(!delete_user_session(user_id, "2", &db_pool).await.unwrap());
+        assert!(get_user_id("22346", &db_pool).await.is_err());
+        assert_eq!(get_user_sessions(user_id, &db_pool).await.unwrap().len(), 2);
+
+        delete_user_sessions(user_id, &db_pool).await.unwrap();
+        assert!(get_user_id("22345", &db_pool).await.is_err());
+        assert!(get_user_id("22347", &db_pool).await.is_err());
+        assert!(get_user_sessions(user_id, &db_pool)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_session_token() {
+        let user_id = 3;
+        let db_pool = create_db_pool().await;
+
+        set_session_token(user_id, "32345", &session_for_test("1"), &db_pool)
+            .await
+            .unwrap();
+        let owner = get_session_owner("32345", &db_pool).await.unwrap();
+        assert_eq!(owner.user_id, user_id);
+        assert_eq!(owner.session_id, "1");
+
+        assert!(rotate_session_token(&owner, "32345", "32346", &db_pool)
+            .await
+            .unwrap());
+        // A token can only be rotated once
+        assert!(!rotate_session_token(&owner, "32345", "32347", &db_pool)
+            .await
+            .unwrap());
+        assert!(get_user_id("32347", &db_pool).await.is_err());
+
+        // The old token is still valid during the grace period
+        let old_owner = get_session_owner("32345", &db_pool).await.unwrap();
+        assert!(old_owner.expires_in <= ROTATED_SESSION_TOKEN_GRACE_PERIOD);
+        let new_owner = get_session_owner("32346", &db_pool).await.unwrap();
+        assert_eq!(new_owner.session_id, "1");
+
+        let sessions = get_user_sessions(user_id, &db_pool).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].0, "32346");
+
+        // Revoking the session revokes both of its tokens
+        delete_session_token("32346", &db_pool).await.unwrap();
+        assert!(get_user_id("32345", &db_pool).await.is_err());
+        assert!(get_user_id("32346", &db_pool).await.is_err());
+
+        // A rotation that gives up doesn't leave the pooled connection watching the token, which
+        // would discard the next transaction made with it
+        set_session_token(user_id, "32348", &session_for_test("2"), &db_pool)
+            .await
+            .unwrap();
+        let owner = SessionOwner {
+            session_id: "missing".to_string(),
+            ..get_session_owner("32348", &db_pool).await.unwrap()
+        };
+        assert!(!rotate_session_token(&owner, "32348", "32349", &db_pool)
+            .await
+            .unwrap());
+        delete_session_token("32348", &db_pool).await.unwrap();
+        set_session_token(user_id, "32350", &session_for_test("3"), &db_pool)
+            .await
+            .unwrap();
+        assert_eq!(get_user_id("32350", &db_pool).await.unwrap(), user_id);
+    }
+
+    #[tokio::test]
+    async fn test_oauth_state() {
+        let db_pool = create_db_pool().await;
+
+        set_oauth_state("state1", Some("/profile/1"), &db_pool)
+            .await
+            .unwrap();
+        set_oauth_state("state2", None, &db_pool).await.unwrap();
+
+        let return_to = take_oauth_state("state1", &db_pool).await.unwrap();
+        assert_eq!(return_to.as_deref(), Some("/profile/1"));
+        let return_to = take_oauth_state("state2", &db_pool).await.unwrap();
+        assert!(return_to.is_none());
+
+        let err = take_oauth_state("state1", &db_pool).await.unwrap_err();
+        assert!(matches!(err, AuthError::ValueNotFound { .. }));
     }
 
     #[tokio::test]
@@ -207,7 +832,7 @@ mod test {
         let refresh_token = "3212345";
         let db_pool = create_db_pool().await;
 
-        set_osu_tokens(user_id, access_token, refresh_token, &db_pool)
+        set_osu_tokens(user_id, access_token, refresh_token, 86400, &db_pool)
             .await
             .unwrap();
         let db_access_token = get_access_token(user_id, &db_pool).await.unwrap();
@@ -215,5 +840,21 @@ mod test {
 
         assert_eq!(access_token, db_access_token);
         assert_eq!(refresh_token, db_refresh_token);
+
+        assert!(lock_osu_token_refresh(user_id, &db_pool).await.unwrap());
+        assert!(!lock_osu_token_refresh(user_id, &db_pool).await.unwrap());
+        assert!(is_osu_token_refresh_locked(user_id, &db_pool)
+            .await
+            .unwrap());
+        unlock_osu_token_refresh(user_id, &db_pool).await.unwrap();
+        assert!(!is_osu_token_refresh_locked(user_id, &db_pool)
+            .await
+            .unwrap());
+        assert!(lock_osu_token_refresh(user_id, &db_pool).await.unwrap());
+        unlock_osu_token_refresh(user_id, &db_pool).await.unwrap();
+
+        delete_osu_tokens(user_id, &db_pool).await.unwrap();
+        assert!(get_access_token(user_id, &db_pool).await.is_err());
+        assert!(get_refresh_token(user_id, &db_pool).await.is_err());
     }
 }
@@ -2,27 +2,62 @@ use chrono::Utc;
 use mi_core::error::{AppErrorExt, ErrorType};
 use mi_core::INTERNAL_DB_ERROR_MESSAGE;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::types::Json;
+use sqlx::{Acquire, FromRow, PgPool, Postgres, QueryBuilder, Transaction};
 use thiserror::Error;
 use tracing::{error, warn};
 use utoipa::ToSchema;
 
-use crate::{PG_FOREIGN_KEY_VIOLATION, PG_UNIQUE_KEY_VIOLATION};
+use crate::influence_tag::replace_influence_tags;
+use crate::{
+    InfluenceAction, InfluenceBeatmap, INFLUENCE_BEATMAP_LIMIT, PG_FOREIGN_KEY_VIOLATION,
+    PG_UNIQUE_KEY_VIOLATION,
+};
+
+/// Number of days a deleted influence can be restored for.
+pub const INFLUENCE_RESTORE_WINDOW_DAYS: i64 = 30;
 
 #[derive(Debug, FromRow, Clone, Serialize, Deserialize, ToSchema, Default)]
 pub struct Influence {
     /// Id of the influencer user
-    from_id: i64,
+    pub from_id: i64,
     /// Id of the influenced user
-    to_id: i64,
+    pub to_id: i64,
     /// Level of influence
-    influence_level: i32,
+    pub influence_level: i32,
     /// Extra info/notes about influence
-    info: Option<String>,
+    pub info: Option<String>,
+    /// Position of the influence in the influencer list of the influenced user, starting from 0
+    pub position: i32,
+    /// Whether the influencer user acknowledged or hid this influence
+    pub acknowledgement: InfluenceAcknowledgement,
+    /// Names of the tags from the influence tag vocabulary
+    pub tags: Vec<String>,
+    /// Example beatmaps attached to the influence
+    #[schema(value_type = Vec<InfluenceBeatmap>)]
+    pub beatmaps: Json<Vec<InfluenceBeatmap>>,
     /// Creation date. Not used during inserts and defaulted
-    created_at: chrono::DateTime<Utc>,
+    pub created_at: chrono::DateTime<Utc>,
     /// Last modification date. Not used during inserts and defaulted
-    modified_at: chrono::DateTime<Utc>,
+    pub modified_at: chrono::DateTime<Utc>,
+    /// Deletion date. Only set on deleted influences that can still be restored
+    pub deleted_at: Option<chrono::DateTime<Utc>>,
+}
+
+/// State of an influence from the point of view of the influencer user (`from_id`).
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type,
+)]
+#[sqlx(type_name = "influence_acknowledgement", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum InfluenceAcknowledgement {
+    /// Influencer user has not responded to the influence yet
+    #[default]
+    Pending,
+    /// Influencer user endorsed the influence
+    Acknowledged,
+    /// Influencer user declined the influence. Hidden influences are excluded from public listings
+    Hidden,
 }
 
 impl Influence {
@@ -35,15 +70,173 @@ impl Influence {
             ..Default::default()
         }
     }
+
+    /// Sets the tags that are written alongside the influence on insert.
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+}
+
+/// Direction of the influence edges to list for a user.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InfluenceDirection {
+    /// Users that influenced the given user. Matches on `to_id`
+    #[default]
+    Influencers,
+    /// Users that are influenced by the given user. Matches on `from_id`
+    Influenced,
+}
+
+impl InfluenceDirection {
+    fn user_column(&self) -> &'static str {
+        match self {
+            InfluenceDirection::Influencers => "to_id",
+            InfluenceDirection::Influenced => "from_id",
+        }
+    }
+}
+
+/// Column to sort influence listings with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InfluenceSort {
+    /// Order set by the influenced user
+    Position,
+    InfluenceLevel,
+    #[default]
+    CreatedAt,
+    ModifiedAt,
+}
+
+impl InfluenceSort {
+    fn column(&self) -> &'static str {
+        match self {
+            InfluenceSort::Position => "position",
+            InfluenceSort::InfluenceLevel => "influence_level",
+            InfluenceSort::CreatedAt => "created_at",
+            InfluenceSort::ModifiedAt => "modified_at",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    fn keyword(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// Options for listing a page of influences of a user.
+#[derive(Debug, Clone)]
+pub struct InfluenceListOptions {
+    pub direction: InfluenceDirection,
+    pub sort: InfluenceSort,
+    pub order: SortOrder,
+    /// Only list the influences that have this tag
+    pub tag: Option<String>,
+    /// User that requests the page. Lists hidden by their owners are only shown to the owners and
+    /// users with hidden profiles are only shown to logged in users
+    pub viewer_id: Option<i64>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Default for InfluenceListOptions {
+    fn default() -> Self {
+        Self {
+            direction: InfluenceDirection::default(),
+            sort: InfluenceSort::default(),
+            order: SortOrder::default(),
+            tag: None,
+            viewer_id: None,
+            limit: 50,
+            offset: 0,
+        }
+    }
+}
+
+/// Returns a page of influences of the given user. Hidden influences and the influences the
+/// viewer is not allowed to see are not included.
+///
+/// Rows are ordered by the requested column first and by the primary key afterwards so that
+/// offsets stay stable between pages.
+pub async fn get_influences_page(
+    user_id: i64,
+    options: InfluenceListOptions,
+    db: &PgPool,
+) -> Result<Vec<Influence>, InfluenceError> {
+    let mut query = QueryBuilder::new(
+        "SELECT *, influence_tag_names(from_id, to_id) AS tags, influence_beatmap_list(from_id, \
+         to_id) AS beatmaps FROM influences WHERE acknowledgement <> 'hidden' AND deleted_at IS \
+         NULL AND ",
+    );
+    query
+        .push(options.direction.user_column())
+        .push(" = ")
+        .push_bind(user_id);
+
+    if let Some(tag) = options.tag {
+        query
+            .push(
+                " AND EXISTS (SELECT 1 FROM influence_tag_links l INNER JOIN influence_tags t ON \
+                 t.id = l.tag_id WHERE l.from_id = influences.from_id AND l.to_id = \
+                 influences.to_id AND t.name = ",
+            )
+            .push_bind(tag)
+            .push(")");
+    }
+
+    query
+        .push(" AND influence_visible_to(from_id, to_id, ")
+        .push_bind(options.viewer_id)
+        .push(")");
+
+    query
+        .push(format_args!(
+            " ORDER BY {} {}, from_id, to_id LIMIT ",
+            options.sort.column(),
+            options.order.keyword()
+        ))
+        .push_bind(options.limit)
+        .push(" OFFSET ")
+        .push_bind(options.offset);
+
+    let search_result = query.build_query_as::<Influence>().fetch_all(db).await;
+
+    match search_result {
+        Ok(influences) => Ok(influences),
+        Err(db_err) => Err(InfluenceError::from(db_err)),
+    }
 }
 
+/// Returns every influence where the given user is the influencer, including hidden ones.
 pub async fn get_all_influences_by_from_id(
     user_id: i64,
     db: &PgPool,
 ) -> Result<Vec<Influence>, InfluenceError> {
     let search_result = sqlx::query_as!(
         Influence,
-        "SELECT * FROM influences WHERE from_id = $1",
+        r#"
+        SELECT
+            from_id, to_id, influence_level, info, position,
+            acknowledgement as "acknowledgement: InfluenceAcknowledgement",
+            influence_tag_names(from_id, to_id) as "tags!",
+            influence_beatmap_list(from_id, to_id) as "beatmaps!: Json<Vec<InfluenceBeatmap>>",
+            created_at, modified_at, deleted_at
+        FROM influences
+        WHERE from_id = $1 AND deleted_at IS NULL"#,
         user_id
     )
     .fetch_all(db)
@@ -55,13 +248,24 @@ pub async fn get_all_influences_by_from_id(
     }
 }
 
+/// Returns every influence where the given user is the influenced user, including the ones the
+/// influencer hid. Meant for reads of the user's own list, such as exports.
 pub async fn get_all_influences_by_to_id(
     user_id: i64,
     db: &PgPool,
 ) -> Result<Vec<Influence>, InfluenceError> {
     let search_result = sqlx::query_as!(
         Influence,
-        "SELECT * FROM influences WHERE to_id = $1",
+        r#"
+        SELECT
+            from_id, to_id, influence_level, info, position,
+            acknowledgement as "acknowledgement: InfluenceAcknowledgement",
+            influence_tag_names(from_id, to_id) as "tags!",
+            influence_beatmap_list(from_id, to_id) as "beatmaps!: Json<Vec<InfluenceBeatmap>>",
+            created_at, modified_at, deleted_at
+        FROM influences
+        WHERE to_id = $1 AND deleted_at IS NULL
+        ORDER BY position, from_id"#,
         user_id
     )
     .fetch_all(db)
@@ -73,51 +277,345 @@ pub async fn get_all_influences_by_to_id(
     }
 }
 
+/// Inserts a new influence with its tags and records its creation on `influence_history`.
+///
+/// The influenced user (`to_id`) is recorded as the actor.
 pub async fn insert_influence(influence: Influence, db: &PgPool) -> Result<(), InfluenceError> {
+    let mut transaction = db.begin().await?;
+
+    remove_deleted_influence(influence.from_id, influence.to_id, &mut transaction).await?;
+    ensure_influence_listing_allowed(influence.from_id, influence.to_id, &mut transaction).await?;
+
     let insert_result = sqlx::query!(
-        "INSERT INTO influences (from_id, to_id, influence_level, info) VALUES ($1, $2, $3, $4) \
-         RETURNING from_id",
+        r#"
+        WITH inserted AS (
+            INSERT INTO influences (from_id, to_id, influence_level, info, position) VALUES (
+                $1, $2, $3, $4,
+                (SELECT COALESCE(MAX(position) + 1, 0) FROM influences WHERE to_id = $2)
+            )
+            RETURNING from_id, to_id, influence_level, info
+        )
+        INSERT INTO influence_history (from_id, to_id, action, actor_id, new_influence_level, new_info)
+        SELECT from_id, to_id, 'create', to_id, influence_level, info FROM inserted
+        RETURNING from_id"#,
         influence.from_id,
         influence.to_id,
         influence.influence_level,
         influence.info,
     )
-    .fetch_one(db)
+    .fetch_one(&mut transaction)
     .await;
 
-    match insert_result {
-        Ok(_) => Ok(()),
-        Err(db_err) if db_err.as_database_error().is_some() => {
-            // We check if db_err can be casted to database_error.
-            // PgError should always return a valid error code.
-            let pg_db_error_code = db_err.as_database_error().unwrap().code().unwrap();
+    if let Err(db_err) = insert_result {
+        return Err(map_insert_error(db_err, influence.from_id, influence.to_id));
+    }
 
-            match pg_db_error_code.as_ref() {
-                PG_UNIQUE_KEY_VIOLATION => Err(InfluenceError::InfluenceAlreadyExists(
-                    influence.from_id,
-                    influence.to_id,
-                )),
-                PG_FOREIGN_KEY_VIOLATION => Err(InfluenceError::UserDoesNotExist(
+    if !influence.tags.is_empty() {
+        replace_influence_tags(
+            influence.from_id,
+            influence.to_id,
+            &influence.tags,
+            &mut transaction,
+        )
+        .await?;
+    }
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Removes a soft deleted influence for good, so that a new influence can take its primary key.
+/// Tags and beatmaps of the deleted influence are removed alongside it.
+///
+/// Deletions that can still be restored are kept, so re-adding an influence doesn't silently
+/// destroy it. Fails with [`InfluenceError::DeletedInfluenceAlreadyExists`] for them instead.
+async fn remove_deleted_influence(
+    from_id: i64,
+    to_id: i64,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), InfluenceError> {
+    let restorable = sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM influences WHERE from_id = $1 AND to_id = $2 AND deleted_at > $3
+        ) as "restorable!""#,
+        from_id,
+        to_id,
+        restore_window_start()
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    if restorable {
+        return Err(InfluenceError::DeletedInfluenceAlreadyExists(
+            from_id, to_id,
+        ));
+    }
+
+    sqlx::query!(
+        "DELETE FROM influences WHERE from_id = $1 AND to_id = $2 AND deleted_at IS NOT NULL",
+        from_id,
+        to_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Fails with [`InfluenceError::InfluenceListingNotAllowed`] if a new influence would list a user
+/// that does not allow being listed. Existing influences can still be updated.
+async fn ensure_influence_listing_allowed(
+    from_id: i64,
+    to_id: i64,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), InfluenceError> {
+    let allowed = sqlx::query!(
+        r#"
+        SELECT
+            NOT EXISTS (
+                SELECT 1 FROM user_profiles WHERE user_id = $1 AND NOT allow_influence_listing
+            ) OR EXISTS (
+                SELECT 1 FROM influences WHERE from_id = $1 AND to_id = $2 AND deleted_at IS NULL
+            ) as "allowed!""#,
+        from_id,
+        to_id
+    )
+    .fetch_one(&mut *transaction)
+    .await?
+    .allowed;
+
+    if !allowed {
+        return Err(InfluenceError::InfluenceListingNotAllowed(from_id));
+    }
+
+    Ok(())
+}
+
+/// Maps key violations returned while writing an influence into their [`InfluenceError`]
+/// counterparts.
+fn map_insert_error(db_err: sqlx::Error, from_id: i64, to_id: i64) -> InfluenceError {
+    if db_err.as_database_error().is_none() {
+        return InfluenceError::from(db_err);
+    }
+
+    // We check if db_err can be casted to database_error.
+    // PgError should always return a valid error code.
+    let pg_db_error_code = db_err.as_database_error().unwrap().code().unwrap();
+
+    match pg_db_error_code.as_ref() {
+        PG_UNIQUE_KEY_VIOLATION => InfluenceError::InfluenceAlreadyExists(from_id, to_id),
+        PG_FOREIGN_KEY_VIOLATION => InfluenceError::UserDoesNotExist(from_id, to_id),
+        _ => InfluenceError::from(db_err),
+    }
+}
+
+/// Creates or updates every given influence in a single transaction.
+///
+/// Each influence is written inside its own savepoint, so a failing row does not abort the rest of
+/// the import. The returned vector has one entry per given influence in the same order, containing
+/// either the action that was recorded on `influence_history` or the error of that row.
+pub async fn upsert_influences(
+    influences: Vec<Influence>,
+    actor_id: i64,
+    db: &PgPool,
+) -> Result<Vec<Result<InfluenceAction, InfluenceError>>, InfluenceError> {
+    let mut transaction = db.begin().await?;
+    let mut results = Vec::with_capacity(influences.len());
+
+    for influence in influences {
+        let mut savepoint = transaction.begin().await?;
+
+        let mut prepare_result =
+            remove_deleted_influence(influence.from_id, influence.to_id, &mut savepoint).await;
+        if prepare_result.is_ok() {
+            prepare_result = ensure_influence_listing_allowed(
+                influence.from_id,
+                influence.to_id,
+                &mut savepoint,
+            )
+            .await;
+        }
+        if let Err(err) = prepare_result {
+            savepoint.rollback().await?;
+            results.push(Err(err));
+            continue;
+        }
+
+        let upsert_result = sqlx::query!(
+            r#"
+            WITH old AS (
+                SELECT influence_level, info FROM influences WHERE from_id = $1 AND to_id = $2
+            ), upserted AS (
+                INSERT INTO influences (from_id, to_id, influence_level, info, position) VALUES (
+                    $1, $2, $3, $4,
+                    (SELECT COALESCE(MAX(position) + 1, 0) FROM influences WHERE to_id = $2)
+                )
+                ON CONFLICT (from_id, to_id) DO UPDATE
+                SET (influence_level, info, modified_at) = (EXCLUDED.influence_level, EXCLUDED.info, DEFAULT)
+                RETURNING from_id, to_id, influence_level, info
+            )
+            INSERT INTO influence_history (
+                from_id, to_id, action, actor_id,
+                old_influence_level, new_influence_level, old_info, new_info
+            )
+            SELECT
+                upserted.from_id, upserted.to_id,
+                CASE WHEN old.influence_level IS NULL THEN 'create' ELSE 'update' END::influence_action,
+                $5, old.influence_level, upserted.influence_level, old.info, upserted.info
+            FROM upserted LEFT JOIN old ON TRUE
+            RETURNING action as "action: InfluenceAction""#,
+            influence.from_id,
+            influence.to_id,
+            influence.influence_level,
+            influence.info,
+            actor_id,
+        )
+        .fetch_one(&mut savepoint)
+        .await;
+
+        match upsert_result {
+            Ok(row) => {
+                savepoint.commit().await?;
+                results.push(Ok(row.action));
+            }
+            Err(db_err) => {
+                savepoint.rollback().await?;
+                results.push(Err(map_insert_error(
+                    db_err,
                     influence.from_id,
                     influence.to_id,
-                )),
-                _ => Err(InfluenceError::from(db_err)),
+                )));
             }
         }
-        Err(db_err) => Err(InfluenceError::from(db_err)),
     }
+
+    transaction.commit().await?;
+    Ok(results)
 }
 
-pub async fn update_influence_level(
+/// A single entry of [`reorder_influences`].
+#[derive(Debug, Clone, Default)]
+pub struct InfluenceReorderEntry {
+    /// Id of the influencer user
+    pub from_id: i64,
+    /// New level of the influence. Left unchanged if `None`
+    pub influence_level: Option<i32>,
+    /// New info of the influence. Left unchanged if `None`
+    pub info: Option<String>,
+}
+
+/// Orders the influencers of the given user as listed in `entries` and applies their level and info
+/// changes in a single transaction. Changes are recorded on `influence_history` with the
+/// influenced user as the actor.
+///
+/// Influences that are not listed keep their relative order and are placed after the listed ones.
+/// If any of the listed influences does not exist, nothing is changed.
+pub async fn reorder_influences(
+    to_id: i64,
+    entries: Vec<InfluenceReorderEntry>,
+    db: &PgPool,
+) -> Result<(), InfluenceError> {
+    let mut transaction = db.begin().await?;
+
+    for entry in &entries {
+        if entry.influence_level.is_none() && entry.info.is_none() {
+            continue;
+        }
+
+        let update_result = sqlx::query!(
+            r#"
+            WITH old AS (
+                SELECT influence_level, info FROM influences
+                WHERE from_id = $1 AND to_id = $2 AND deleted_at IS NULL
+            ), updated AS (
+                UPDATE influences
+                SET (influence_level, info, modified_at) = (COALESCE($3, influence_level), COALESCE($4, info), DEFAULT)
+                WHERE from_id = $1 AND to_id = $2 AND deleted_at IS NULL
+                RETURNING from_id, to_id, influence_level, info
+            )
+            INSERT INTO influence_history (
+                from_id, to_id, action, actor_id,
+                old_influence_level, new_influence_level, old_info, new_info
+            )
+            SELECT
+                updated.from_id, updated.to_id, 'update', $2,
+                old.influence_level, updated.influence_level, old.info, updated.info
+            FROM updated, old
+            RETURNING from_id"#,
+            entry.from_id,
+            to_id,
+            entry.influence_level,
+            entry.info,
+        )
+        .fetch_one(&mut transaction)
+        .await;
+
+        match update_result {
+            Ok(_) => {}
+            Err(sqlx::Error::RowNotFound) => {
+                return Err(InfluenceError::InfluenceNotFoundWithPrimaryKey(
+                    entry.from_id,
+                    to_id,
+                ))
+            }
+            Err(db_err) => return Err(InfluenceError::from(db_err)),
+        }
+    }
+
+    let from_ids: Vec<i64> = entries.iter().map(|entry| entry.from_id).collect();
+    let existing = sqlx::query!(
+        "SELECT from_id FROM influences WHERE to_id = $1 AND from_id = ANY($2) AND deleted_at IS \
+         NULL FOR UPDATE",
+        to_id,
+        &from_ids
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+
+    if let Some(missing) = from_ids
+        .iter()
+        .find(|from_id| !existing.iter().any(|row| row.from_id == **from_id))
+    {
+        return Err(InfluenceError::InfluenceNotFoundWithPrimaryKey(
+            *missing, to_id,
+        ));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE influences SET position = ordered.position
+        FROM (
+            SELECT
+                from_id,
+                (ROW_NUMBER() OVER (
+                    ORDER BY array_position($2::BIGINT[], from_id) NULLS LAST, position, from_id
+                ) - 1)::INT AS position
+            FROM influences
+            WHERE to_id = $1 AND deleted_at IS NULL
+        ) ordered
+        WHERE influences.to_id = $1 AND influences.from_id = ordered.from_id"#,
+        to_id,
+        &from_ids
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Updates the acknowledgement state of an influence. Only the influencer user (`from_id`) is
+/// supposed to call this.
+pub async fn update_influence_acknowledgement(
     from_id: i64,
     to_id: i64,
-    influence_level: i32,
+    acknowledgement: InfluenceAcknowledgement,
     db: &PgPool,
 ) -> Result<(), InfluenceError> {
     let update_result = sqlx::query!(
-        "UPDATE influences SET (influence_level, modified_at) = ($1, DEFAULT) WHERE from_id = $2 \
-         AND to_id = $3 RETURNING from_id",
-        influence_level,
+        "UPDATE influences SET acknowledgement = $1 WHERE from_id = $2 AND to_id = $3 AND \
+         deleted_at IS NULL RETURNING from_id",
+        acknowledgement as InfluenceAcknowledgement,
         from_id,
         to_id
     )
@@ -133,18 +631,38 @@ pub async fn update_influence_level(
     }
 }
 
-pub async fn update_influence_info(
+/// Updates the level of an influence and records the old and new values on
+/// `influence_history`.
+pub async fn update_influence_level(
     from_id: i64,
     to_id: i64,
-    info: Option<&str>,
+    influence_level: i32,
+    actor_id: i64,
     db: &PgPool,
 ) -> Result<(), InfluenceError> {
     let update_result = sqlx::query!(
-        "UPDATE influences SET (info, modified_at) = ($1, DEFAULT) WHERE from_id = $2 AND to_id = \
-         $3 RETURNING from_id",
-        info,
+        r#"
+        WITH old AS (
+            SELECT influence_level, info FROM influences
+            WHERE from_id = $2 AND to_id = $3 AND deleted_at IS NULL
+        ), updated AS (
+            UPDATE influences SET (influence_level, modified_at) = ($1, DEFAULT)
+            WHERE from_id = $2 AND to_id = $3 AND deleted_at IS NULL
+            RETURNING from_id, to_id, influence_level, info
+        )
+        INSERT INTO influence_history (
+            from_id, to_id, action, actor_id,
+            old_influence_level, new_influence_level, old_info, new_info
+        )
+        SELECT
+            updated.from_id, updated.to_id, 'update', $4,
+            old.influence_level, updated.influence_level, old.info, updated.info
+        FROM updated, old
+        RETURNING from_id"#,
+        influence_level,
         from_id,
-        to_id
+        to_id,
+        actor_id,
     )
     .fetch_one(db)
     .await;
@@ -158,12 +676,111 @@ pub async fn update_influence_info(
     }
 }
 
-pub async fn delete_influence(from_id: i64, to_id: i64, db: &PgPool) -> Result<(), InfluenceError> {
-    let delete_result = sqlx::query!(
-        "DELETE FROM influences WHERE from_id = $1 AND to_id = $2 RETURNING from_id",
+/// Updates the info of an influence, and its tags if given, in a single transaction. The old and
+/// new values are recorded on `influence_history`. Tag columns of the history row are left null if
+/// the tags are not changed.
+///
+/// Fails with [`InfluenceError::UnknownInfluenceTags`] if any of the given tags is not in the
+/// vocabulary, in which case nothing is changed.
+pub async fn update_influence_info(
+    from_id: i64,
+    to_id: i64,
+    info: Option<&str>,
+    tags: Option<&[String]>,
+    actor_id: i64,
+    db: &PgPool,
+) -> Result<(), InfluenceError> {
+    let mut transaction = db.begin().await?;
+
+    let old = sqlx::query!(
+        r#"
+        SELECT
+            influence_level, info,
+            influence_tag_names(from_id, to_id) as "tags!"
+        FROM influences
+        WHERE from_id = $1 AND to_id = $2 AND deleted_at IS NULL
+        FOR UPDATE"#,
         from_id,
         to_id
     )
+    .fetch_optional(&mut transaction)
+    .await?
+    .ok_or(InfluenceError::InfluenceNotFoundWithPrimaryKey(
+        from_id, to_id,
+    ))?;
+
+    let (old_tags, new_tags) = match tags {
+        Some(tags) => {
+            replace_influence_tags(from_id, to_id, tags, &mut transaction).await?;
+            let new_tags = sqlx::query_scalar!(
+                r#"
+                SELECT influence_tag_names($1, $2) as "tags!""#,
+                from_id,
+                to_id
+            )
+            .fetch_one(&mut transaction)
+            .await?;
+            (Some(old.tags), Some(new_tags))
+        }
+        None => (None, None),
+    };
+
+    sqlx::query!(
+        r#"
+        WITH updated AS (
+            UPDATE influences SET (info, modified_at) = ($1, DEFAULT)
+            WHERE from_id = $2 AND to_id = $3
+            RETURNING from_id, to_id, influence_level, info
+        )
+        INSERT INTO influence_history (
+            from_id, to_id, action, actor_id,
+            old_influence_level, new_influence_level, old_info, new_info, old_tags, new_tags
+        )
+        SELECT
+            from_id, to_id, 'update', $4,
+            $5, influence_level, $6, info, $7, $8
+        FROM updated"#,
+        info,
+        from_id,
+        to_id,
+        actor_id,
+        old.influence_level,
+        old.info,
+        old_tags.as_deref(),
+        new_tags.as_deref(),
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Soft deletes an influence and records its last values on `influence_history`.
+///
+/// Deleted influences are excluded from every read and can be restored with [`restore_influence`]
+/// for [`INFLUENCE_RESTORE_WINDOW_DAYS`] days. Afterwards they are removed for good by
+/// [`purge_deleted_influences`].
+pub async fn delete_influence(
+    from_id: i64,
+    to_id: i64,
+    actor_id: i64,
+    db: &PgPool,
+) -> Result<(), InfluenceError> {
+    let delete_result = sqlx::query!(
+        r#"
+        WITH deleted AS (
+            UPDATE influences SET deleted_at = CURRENT_TIMESTAMP
+            WHERE from_id = $1 AND to_id = $2 AND deleted_at IS NULL
+            RETURNING from_id, to_id, influence_level, info
+        )
+        INSERT INTO influence_history (from_id, to_id, action, actor_id, old_influence_level, old_info)
+        SELECT from_id, to_id, 'delete', $3, influence_level, info FROM deleted
+        RETURNING from_id"#,
+        from_id,
+        to_id,
+        actor_id,
+    )
     .fetch_one(db)
     .await;
 
@@ -176,6 +793,89 @@ pub async fn delete_influence(from_id: i64, to_id: i64, db: &PgPool) -> Result<(
     }
 }
 
+/// Restores a soft deleted influence if it was deleted in the last
+/// [`INFLUENCE_RESTORE_WINDOW_DAYS`] days and records it on `influence_history`.
+pub async fn restore_influence(
+    from_id: i64,
+    to_id: i64,
+    actor_id: i64,
+    db: &PgPool,
+) -> Result<(), InfluenceError> {
+    let restore_result = sqlx::query!(
+        r#"
+        WITH restored AS (
+            UPDATE influences SET deleted_at = NULL
+            WHERE from_id = $1 AND to_id = $2 AND deleted_at > $4
+            RETURNING from_id, to_id, influence_level, info
+        )
+        INSERT INTO influence_history (from_id, to_id, action, actor_id, new_influence_level, new_info)
+        SELECT from_id, to_id, 'restore', $3, influence_level, info FROM restored
+        RETURNING from_id"#,
+        from_id,
+        to_id,
+        actor_id,
+        restore_window_start(),
+    )
+    .fetch_one(db)
+    .await;
+
+    match restore_result {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::RowNotFound) => {
+            Err(InfluenceError::DeletedInfluenceNotFound(from_id, to_id))
+        }
+        Err(db_err) => Err(InfluenceError::from(db_err)),
+    }
+}
+
+/// Returns the influences of the given user that are deleted but can still be restored, most
+/// recently deleted first.
+pub async fn get_deleted_influences(
+    to_id: i64,
+    db: &PgPool,
+) -> Result<Vec<Influence>, InfluenceError> {
+    let search_result = sqlx::query_as!(
+        Influence,
+        r#"
+        SELECT
+            from_id, to_id, influence_level, info, position,
+            acknowledgement as "acknowledgement: InfluenceAcknowledgement",
+            influence_tag_names(from_id, to_id) as "tags!",
+            influence_beatmap_list(from_id, to_id) as "beatmaps!: Json<Vec<InfluenceBeatmap>>",
+            created_at, modified_at, deleted_at
+        FROM influences
+        WHERE to_id = $1 AND deleted_at > $2
+        ORDER BY deleted_at DESC"#,
+        to_id,
+        restore_window_start(),
+    )
+    .fetch_all(db)
+    .await;
+
+    match search_result {
+        Ok(influences) => Ok(influences),
+        Err(db_err) => Err(InfluenceError::from(db_err)),
+    }
+}
+
+/// Removes the influences that were deleted before the restore window for good. Returns the number
+/// of removed influences.
+pub async fn purge_deleted_influences(db: &PgPool) -> Result<u64, InfluenceError> {
+    let purge_result = sqlx::query!(
+        "DELETE FROM influences WHERE deleted_at <= $1",
+        restore_window_start()
+    )
+    .execute(db)
+    .await?;
+
+    Ok(purge_result.rows_affected())
+}
+
+/// Oldest deletion date that can still be restored.
+fn restore_window_start() -> chrono::DateTime<Utc> {
+    Utc::now() - chrono::Duration::days(INFLUENCE_RESTORE_WINDOW_DAYS)
+}
+
 #[derive(Debug, Error)]
 pub enum InfluenceError {
     #[error("No influence with from_id `{0}` and to_id `{1}` exists.")]
@@ -184,6 +884,26 @@ pub enum InfluenceError {
     UserDoesNotExist(i64, i64),
     #[error("Influence already exist from user `{0}` to user `{1}`")]
     InfluenceAlreadyExists(i64, i64),
+    #[error(
+        "Influence from user `{0}` to user `{1}` was deleted in the last {} days. Restore it \
+         through /influence/restore instead.",
+        INFLUENCE_RESTORE_WINDOW_DAYS
+    )]
+    DeletedInfluenceAlreadyExists(i64, i64),
+    #[error(
+        "No influence with from_id `{0}` and to_id `{1}` was deleted in the last {} days.",
+        INFLUENCE_RESTORE_WINDOW_DAYS
+    )]
+    DeletedInfluenceNotFound(i64, i64),
+    #[error("Unknown influence tags: {}", .0.join(", "))]
+    UnknownInfluenceTags(Vec<String>),
+    #[error(
+        "An influence can have at most {} beatmaps, got {0}",
+        INFLUENCE_BEATMAP_LIMIT
+    )]
+    TooManyInfluenceBeatmaps(usize),
+    #[error("User `{0}` does not allow being listed as an influence.")]
+    InfluenceListingNotAllowed(i64),
     #[error("Internal database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
 }
@@ -194,6 +914,11 @@ impl AppErrorExt for InfluenceError {
             InfluenceError::InfluenceNotFoundWithPrimaryKey(_, _) => self.to_string(),
             InfluenceError::UserDoesNotExist(_, _) => self.to_string(),
             InfluenceError::InfluenceAlreadyExists(_, _) => self.to_string(),
+            InfluenceError::DeletedInfluenceAlreadyExists(_, _) => self.to_string(),
+            InfluenceError::DeletedInfluenceNotFound(_, _) => self.to_string(),
+            InfluenceError::UnknownInfluenceTags(_) => self.to_string(),
+            InfluenceError::TooManyInfluenceBeatmaps(_) => self.to_string(),
+            InfluenceError::InfluenceListingNotAllowed(_) => self.to_string(),
             InfluenceError::DatabaseError(_) => INTERNAL_DB_ERROR_MESSAGE.to_string(),
         }
     }
@@ -203,6 +928,11 @@ impl AppErrorExt for InfluenceError {
             InfluenceError::InfluenceNotFoundWithPrimaryKey(_, _) => ErrorType::DataNotFound,
             InfluenceError::UserDoesNotExist(_, _) => ErrorType::DataNotFound,
             InfluenceError::InfluenceAlreadyExists(_, _) => ErrorType::DuplicateEntry,
+            InfluenceError::DeletedInfluenceAlreadyExists(_, _) => ErrorType::DuplicateEntry,
+            InfluenceError::DeletedInfluenceNotFound(_, _) => ErrorType::DataNotFound,
+            InfluenceError::UnknownInfluenceTags(_) => ErrorType::BadRequestData,
+            InfluenceError::TooManyInfluenceBeatmaps(_) => ErrorType::BadRequestData,
+            InfluenceError::InfluenceListingNotAllowed(_) => ErrorType::BadRequestData,
             InfluenceError::DatabaseError(_) => ErrorType::DatabaseError,
         }
     }
@@ -218,6 +948,17 @@ impl AppErrorExt for InfluenceError {
             InfluenceError::InfluenceAlreadyExists(from_id, to_id) => {
                 warn!(from_id, to_id, "{}", self.to_string())
             }
+            InfluenceError::DeletedInfluenceAlreadyExists(from_id, to_id) => {
+                warn!(from_id, to_id, "{}", self.to_string())
+            }
+            InfluenceError::DeletedInfluenceNotFound(from_id, to_id) => {
+                warn!(from_id, to_id, "{}", self.to_string())
+            }
+            InfluenceError::UnknownInfluenceTags(_) => warn!("{}", self.to_string()),
+            InfluenceError::TooManyInfluenceBeatmaps(_) => warn!("{}", self.to_string()),
+            InfluenceError::InfluenceListingNotAllowed(user_id) => {
+                warn!(user_id, "{}", self.to_string())
+            }
             InfluenceError::DatabaseError(db_err) => error!("{}", db_err),
         }
     }
@@ -228,7 +969,7 @@ mod tests {
     use sqlx::PgPool;
 
     use super::*;
-    use crate::user::{init_user, User};
+    use crate::user::{init_user, update_privacy_settings, PrivacySettings, User};
 
     fn user_for_test(user_id: i64) -> User {
         User::new(
@@ -299,7 +1040,7 @@ mod tests {
             _ => panic!("Should return foreign_key_violation error."),
         }
 
-        delete_influence(second_user.id, first_user.id, &db)
+        delete_influence(second_user.id, first_user.id, first_user.id, &db)
             .await
             .unwrap();
         let response = get_all_influences_by_from_id(second_user.id, &db)
@@ -308,10 +1049,17 @@ mod tests {
 
         assert!(response.is_empty());
 
-        update_influence_info(first_user.id, second_user.id, Some("Some info"), &db)
-            .await
-            .unwrap();
-        update_influence_level(first_user.id, second_user.id, 1, &db)
+        update_influence_info(
+            first_user.id,
+            second_user.id,
+            Some("Some info"),
+            None,
+            second_user.id,
+            &db,
+        )
+        .await
+        .unwrap();
+        update_influence_level(first_user.id, second_user.id, 1, second_user.id, &db)
             .await
             .unwrap();
 
@@ -324,4 +1072,396 @@ mod tests {
         assert_eq!(influence[0].influence_level, 1);
         assert_eq!(influence[0].info, Some("Some info".to_string()));
     }
+
+    #[sqlx::test]
+    async fn test_influence_soft_delete(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+        insert_influence(
+            Influence::new(1, 2, 4, Some("Carefully written info".to_string())),
+            &db,
+        )
+        .await
+        .unwrap();
+
+        delete_influence(1, 2, 2, &db).await.unwrap();
+
+        assert!(get_all_influences_by_to_id(2, &db)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(get_all_influences_by_from_id(1, &db)
+            .await
+            .unwrap()
+            .is_empty());
+        let error = update_influence_level(1, 2, 5, 2, &db).await.unwrap_err();
+        assert!(matches!(
+            error,
+            InfluenceError::InfluenceNotFoundWithPrimaryKey(1, 2)
+        ));
+
+        let deleted = get_deleted_influences(2, &db).await.unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert!(deleted[0].deleted_at.is_some());
+
+        restore_influence(1, 2, 2, &db).await.unwrap();
+
+        let influences = get_all_influences_by_to_id(2, &db).await.unwrap();
+        assert_eq!(influences.len(), 1);
+        assert_eq!(
+            influences[0].info,
+            Some("Carefully written info".to_string())
+        );
+
+        let error = restore_influence(1, 2, 2, &db).await.unwrap_err();
+        assert!(matches!(
+            error,
+            InfluenceError::DeletedInfluenceNotFound(1, 2)
+        ));
+
+        // Deletions older than the restore window are purged and can't be restored
+        delete_influence(1, 2, 2, &db).await.unwrap();
+        sqlx::query!(
+            "UPDATE influences SET deleted_at = deleted_at - $1::INTERVAL",
+            sqlx::postgres::types::PgInterval {
+                months: 0,
+                days: INFLUENCE_RESTORE_WINDOW_DAYS as i32 + 1,
+                microseconds: 0,
+            }
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        assert!(restore_influence(1, 2, 2, &db).await.is_err());
+        assert_eq!(purge_deleted_influences(&db).await.unwrap(), 1);
+
+        // A deletion that can't be restored anymore does not block creating it again
+        insert_influence(influence_for_test(1, 2), &db)
+            .await
+            .unwrap();
+        assert_eq!(get_all_influences_by_to_id(2, &db).await.unwrap().len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn test_influence_readd_after_delete(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+        insert_influence(
+            Influence::new(1, 2, 4, Some("Carefully written info".to_string())),
+            &db,
+        )
+        .await
+        .unwrap();
+        delete_influence(1, 2, 2, &db).await.unwrap();
+
+        // Re-adding inside the restore window keeps the deleted influence
+        let error = insert_influence(influence_for_test(1, 2), &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            InfluenceError::DeletedInfluenceAlreadyExists(1, 2)
+        ));
+        let results = upsert_influences(vec![influence_for_test(1, 2)], 2, &db)
+            .await
+            .unwrap();
+        assert!(matches!(
+            results[0],
+            Err(InfluenceError::DeletedInfluenceAlreadyExists(1, 2))
+        ));
+
+        restore_influence(1, 2, 2, &db).await.unwrap();
+        let influences = get_all_influences_by_to_id(2, &db).await.unwrap();
+        assert_eq!(
+            influences[0].info,
+            Some("Carefully written info".to_string())
+        );
+
+        // Deletions outside the window are replaced by the new influence
+        delete_influence(1, 2, 2, &db).await.unwrap();
+        sqlx::query!(
+            "UPDATE influences SET deleted_at = deleted_at - $1::INTERVAL",
+            sqlx::postgres::types::PgInterval {
+                months: 0,
+                days: INFLUENCE_RESTORE_WINDOW_DAYS as i32 + 1,
+                microseconds: 0,
+            }
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        insert_influence(influence_for_test(1, 2), &db)
+            .await
+            .unwrap();
+        let influences = get_all_influences_by_to_id(2, &db).await.unwrap();
+        assert_eq!(influences.len(), 1);
+        assert_eq!(influences[0].info, None);
+    }
+
+    #[sqlx::test]
+    async fn test_influences_page(db: PgPool) {
+        for user_id in 1..=4 {
+            init_user(user_for_test(user_id), &db).await.unwrap();
+        }
+        for (from_id, level) in [(2, 5), (3, 9), (4, 1)] {
+            insert_influence(Influence::new(from_id, 1, level, None), &db)
+                .await
+                .unwrap();
+        }
+        insert_influence(Influence::new(1, 2, 3, None), &db)
+            .await
+            .unwrap();
+
+        let options = InfluenceListOptions {
+            sort: InfluenceSort::InfluenceLevel,
+            order: SortOrder::Desc,
+            limit: 2,
+            ..Default::default()
+        };
+        let page = get_influences_page(1, options.clone(), &db).await.unwrap();
+
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].from_id, 3);
+        assert_eq!(page[1].from_id, 2);
+
+        let page = get_influences_page(
+            1,
+            InfluenceListOptions {
+                offset: 2,
+                ..options
+            },
+            &db,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].from_id, 4);
+
+        let options = InfluenceListOptions {
+            direction: InfluenceDirection::Influenced,
+            ..Default::default()
+        };
+        let page = get_influences_page(1, options, &db).await.unwrap();
+
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].to_id, 2);
+    }
+
+    #[sqlx::test]
+    async fn test_influence_acknowledgement(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+        insert_influence(influence_for_test(1, 2), &db)
+            .await
+            .unwrap();
+
+        let influences = get_all_influences_by_from_id(1, &db).await.unwrap();
+        assert_eq!(
+            influences[0].acknowledgement,
+            InfluenceAcknowledgement::Pending
+        );
+
+        update_influence_acknowledgement(1, 2, InfluenceAcknowledgement::Hidden, &db)
+            .await
+            .unwrap();
+
+        let page = get_influences_page(2, InfluenceListOptions::default(), &db)
+            .await
+            .unwrap();
+        assert!(page.is_empty());
+        // Influenced user still sees hidden influences on their own list
+        let influences = get_all_influences_by_to_id(2, &db).await.unwrap();
+        assert_eq!(
+            influences[0].acknowledgement,
+            InfluenceAcknowledgement::Hidden
+        );
+        // Influencer still sees hidden influences
+        let influences = get_all_influences_by_from_id(1, &db).await.unwrap();
+        assert_eq!(
+            influences[0].acknowledgement,
+            InfluenceAcknowledgement::Hidden
+        );
+
+        let error = update_influence_acknowledgement(2, 1, InfluenceAcknowledgement::Hidden, &db)
+            .await
+            .unwrap_err();
+        match error {
+            InfluenceError::InfluenceNotFoundWithPrimaryKey(2, 1) => {}
+            _ => panic!("Should return not found error."),
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_reorder_influences(db: PgPool) {
+        for user_id in 1..=4 {
+            init_user(user_for_test(user_id), &db).await.unwrap();
+        }
+        for from_id in 2..=4 {
+            insert_influence(influence_for_test(from_id, 1), &db)
+                .await
+                .unwrap();
+        }
+
+        let influences = get_all_influences_by_to_id(1, &db).await.unwrap();
+        let order: Vec<i64> = influences.iter().map(|i| i.from_id).collect();
+        assert_eq!(order, vec![2, 3, 4]);
+
+        reorder_influences(
+            1,
+            vec![
+                InfluenceReorderEntry {
+                    from_id: 4,
+                    influence_level: Some(9),
+                    ..Default::default()
+                },
+                InfluenceReorderEntry {
+                    from_id: 2,
+                    info: Some("Second".to_string()),
+                    ..Default::default()
+                },
+            ],
+            &db,
+        )
+        .await
+        .unwrap();
+
+        let influences = get_all_influences_by_to_id(1, &db).await.unwrap();
+        let order: Vec<i64> = influences.iter().map(|i| i.from_id).collect();
+        assert_eq!(order, vec![4, 2, 3]);
+        assert_eq!(influences[0].influence_level, 9);
+        assert_eq!(influences[1].info, Some("Second".to_string()));
+        assert_eq!(influences[2].position, 2);
+
+        let error = reorder_influences(
+            1,
+            vec![
+                InfluenceReorderEntry {
+                    from_id: 3,
+                    influence_level: Some(1),
+                    ..Default::default()
+                },
+                InfluenceReorderEntry {
+                    from_id: 5,
+                    ..Default::default()
+                },
+            ],
+            &db,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            InfluenceError::InfluenceNotFoundWithPrimaryKey(5, 1)
+        ));
+
+        // Failed reorder should not change anything
+        let influences = get_all_influences_by_to_id(1, &db).await.unwrap();
+        let order: Vec<i64> = influences.iter().map(|i| i.from_id).collect();
+        assert_eq!(order, vec![4, 2, 3]);
+        assert_eq!(influences[2].influence_level, 3);
+    }
+
+    #[sqlx::test]
+    async fn test_upsert_influences(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+        init_user(user_for_test(3), &db).await.unwrap();
+
+        insert_influence(influence_for_test(2, 1), &db)
+            .await
+            .unwrap();
+
+        let results = upsert_influences(
+            vec![
+                Influence::new(2, 1, 7, Some("Updated".to_string())),
+                influence_for_test(3, 1),
+                influence_for_test(4, 1), // No user with id 4
+            ],
+            1,
+            &db,
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(results[0], Ok(InfluenceAction::Update)));
+        assert!(matches!(results[1], Ok(InfluenceAction::Create)));
+        assert!(matches!(
+            results[2],
+            Err(InfluenceError::UserDoesNotExist(4, 1))
+        ));
+
+        let influences = get_all_influences_by_to_id(1, &db).await.unwrap();
+
+        assert_eq!(influences.len(), 2);
+        let updated = influences.iter().find(|i| i.from_id == 2).unwrap();
+        assert_eq!(updated.influence_level, 7);
+        assert_eq!(updated.info, Some("Updated".to_string()));
+    }
+
+    #[sqlx::test]
+    async fn test_influence_privacy(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+        init_user(user_for_test(3), &db).await.unwrap();
+
+        insert_influence(influence_for_test(1, 2), &db)
+            .await
+            .unwrap();
+
+        let settings = PrivacySettings {
+            allow_influence_listing: false,
+            ..Default::default()
+        };
+        update_privacy_settings(1, settings, &db).await.unwrap();
+
+        let error = insert_influence(influence_for_test(1, 3), &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            InfluenceError::InfluenceListingNotAllowed(1)
+        ));
+
+        // Existing influences can still be updated
+        let results = upsert_influences(
+            vec![Influence::new(1, 2, 7, None), influence_for_test(1, 3)],
+            2,
+            &db,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(results[0], Ok(InfluenceAction::Update)));
+        assert!(matches!(
+            results[1],
+            Err(InfluenceError::InfluenceListingNotAllowed(1))
+        ));
+
+        let settings = PrivacySettings {
+            hide_influences: true,
+            ..Default::default()
+        };
+        update_privacy_settings(2, settings, &db).await.unwrap();
+
+        let page = get_influences_page(2, InfluenceListOptions::default(), &db)
+            .await
+            .unwrap();
+        assert!(page.is_empty());
+
+        let options = InfluenceListOptions {
+            viewer_id: Some(2),
+            ..Default::default()
+        };
+        let page = get_influences_page(2, options, &db).await.unwrap();
+        assert_eq!(page.len(), 1);
+
+        // Mentions on a hidden list are hidden as well
+        let options = InfluenceListOptions {
+            direction: InfluenceDirection::Influenced,
+            viewer_id: Some(3),
+            ..Default::default()
+        };
+        let page = get_influences_page(1, options, &db).await.unwrap();
+        assert!(page.is_empty());
+    }
 }
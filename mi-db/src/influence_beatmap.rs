@@ -0,0 +1,183 @@
+use mi_osu_api::{Beatmap, Beatmapset, BeatmapsetNames, Covers};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::InfluenceError;
+
+/// Maximum number of beatmaps that can be attached to a single influence.
+pub const INFLUENCE_BEATMAP_LIMIT: usize = 5;
+
+/// A beatmapset, or a single difficulty of it, attached to an influence as an example.
+///
+/// Stored as it was when it got attached so listings don't need to reach osu! API.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InfluenceBeatmap {
+    /// Id of the beatmapset
+    pub beatmapset_id: i64,
+    /// Id of the difficulty. Null if the whole beatmapset is attached
+    pub beatmap_id: Option<i64>,
+    /// Name of the difficulty. Null if the whole beatmapset is attached
+    pub difficulty_name: Option<String>,
+    /// Name of the mapper of the beatmapset
+    pub creator: String,
+    pub names: BeatmapsetNames,
+    pub covers: Covers,
+}
+
+impl InfluenceBeatmap {
+    /// Creates an entry for the whole beatmapset.
+    pub fn from_beatmapset(beatmapset: Beatmapset) -> Self {
+        Self {
+            beatmapset_id: beatmapset.id,
+            beatmap_id: None,
+            difficulty_name: None,
+            creator: beatmapset.creator,
+            names: beatmapset.names,
+            covers: beatmapset.covers,
+        }
+    }
+
+    /// Creates an entry for a single difficulty of the given beatmapset.
+    pub fn from_beatmap(beatmap: Beatmap, beatmapset: Beatmapset) -> Self {
+        Self {
+            beatmap_id: Some(beatmap.id),
+            difficulty_name: Some(beatmap.name),
+            ..Self::from_beatmapset(beatmapset)
+        }
+    }
+}
+
+/// Replaces the beatmaps attached to an influence. Beatmaps are listed in the given order.
+///
+/// Fails with [`InfluenceError::TooManyInfluenceBeatmaps`] if more than
+/// [`INFLUENCE_BEATMAP_LIMIT`] beatmaps are given.
+pub async fn set_influence_beatmaps(
+    from_id: i64,
+    to_id: i64,
+    beatmaps: Vec<InfluenceBeatmap>,
+    db: &PgPool,
+) -> Result<(), InfluenceError> {
+    if beatmaps.len() > INFLUENCE_BEATMAP_LIMIT {
+        return Err(InfluenceError::TooManyInfluenceBeatmaps(beatmaps.len()));
+    }
+
+    let mut transaction = db.begin().await?;
+
+    let influence_exists = sqlx::query!(
+        "SELECT from_id FROM influences WHERE from_id = $1 AND to_id = $2 AND deleted_at IS NULL \
+         FOR UPDATE",
+        from_id,
+        to_id
+    )
+    .fetch_optional(&mut transaction)
+    .await?
+    .is_some();
+
+    if !influence_exists {
+        return Err(InfluenceError::InfluenceNotFoundWithPrimaryKey(
+            from_id, to_id,
+        ));
+    }
+
+    sqlx::query!(
+        "DELETE FROM influence_beatmaps WHERE from_id = $1 AND to_id = $2",
+        from_id,
+        to_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    for (position, beatmap) in beatmaps.into_iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO influence_beatmaps (from_id, to_id, position, beatmap) VALUES ($1, $2, \
+             $3, $4)",
+            from_id,
+            to_id,
+            position as i32,
+            Json(beatmap) as _
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::influence::{get_all_influences_by_to_id, insert_influence, Influence};
+    use crate::user::{init_user, User};
+
+    fn user_for_test(user_id: i64) -> User {
+        User::new(
+            user_id,
+            "boraarslan".to_string(),
+            "random.imageservice.com/boraarslan.jpg".to_string(),
+        )
+    }
+
+    fn beatmap_for_test(beatmapset_id: i64) -> InfluenceBeatmap {
+        InfluenceBeatmap {
+            beatmapset_id,
+            beatmap_id: None,
+            difficulty_name: None,
+            creator: "boraarslan".to_string(),
+            names: BeatmapsetNames {
+                artist: "Artist".to_string(),
+                artist_unicode: "Artist".to_string(),
+                title: "Title".to_string(),
+                title_unicode: "Title".to_string(),
+            },
+            covers: Covers {
+                cover: "cover.jpg".to_string(),
+                card: "card.jpg".to_string(),
+                list: "list.jpg".to_string(),
+                slimcover: "slimcover.jpg".to_string(),
+            },
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_influence_beatmaps(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+        insert_influence(Influence::new(1, 2, 5, None), &db)
+            .await
+            .unwrap();
+
+        set_influence_beatmaps(1, 2, vec![beatmap_for_test(10), beatmap_for_test(5)], &db)
+            .await
+            .unwrap();
+
+        let influences = get_all_influences_by_to_id(2, &db).await.unwrap();
+        assert_eq!(influences[0].beatmaps.len(), 2);
+        assert_eq!(influences[0].beatmaps[0].beatmapset_id, 10);
+        assert_eq!(influences[0].beatmaps[1].beatmapset_id, 5);
+
+        let too_many = (0..=INFLUENCE_BEATMAP_LIMIT as i64)
+            .map(beatmap_for_test)
+            .collect();
+        let error = set_influence_beatmaps(1, 2, too_many, &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, InfluenceError::TooManyInfluenceBeatmaps(_)));
+
+        let error = set_influence_beatmaps(2, 1, vec![beatmap_for_test(1)], &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            InfluenceError::InfluenceNotFoundWithPrimaryKey(2, 1)
+        ));
+
+        set_influence_beatmaps(1, 2, Vec::new(), &db).await.unwrap();
+        let influences = get_all_influences_by_to_id(2, &db).await.unwrap();
+        assert!(influences[0].beatmaps.is_empty());
+    }
+}
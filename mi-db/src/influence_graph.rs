@@ -0,0 +1,633 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+
+use crate::{InfluenceDirection, InfluenceError};
+
+/// An influence edge that is reached while walking the influence graph.
+#[derive(Debug, FromRow, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct InfluenceEdge {
+    /// Id of the influencer user
+    pub from_id: i64,
+    /// Id of the influenced user
+    pub to_id: i64,
+    /// Level of influence
+    pub influence_level: i32,
+    /// Number of hops between the root user and this edge. Edges of the root user have depth 1
+    pub depth: i32,
+}
+
+/// A user that is reached while walking the influence graph.
+#[derive(Debug, FromRow, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct InfluenceNode {
+    /// Osu user ID of a user
+    pub id: i64,
+    /// Last known user name of the user
+    pub user_name: String,
+    /// Url to user profile picture
+    pub profile_picture: String,
+    /// Minimum number of hops between the root user and this user. Root user has depth 0
+    pub depth: i32,
+}
+
+/// N-hop influence tree of a user.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InfluenceGraph {
+    /// Id of the user that the graph is built around
+    pub root_id: i64,
+    pub nodes: Vec<InfluenceNode>,
+    pub edges: Vec<InfluenceEdge>,
+}
+
+/// Maximum number of users that are expanded on a single level of the influence graph. Users
+/// reached beyond it are still returned, but their own influences are not walked.
+const INFLUENCE_GRAPH_MAX_FRONTIER: usize = 200;
+
+/// Walks the influence graph starting from `user_id` up to `max_depth` hops.
+///
+/// [`InfluenceDirection::Influencers`] walks the ancestry of the user (who influenced the user,
+/// who influenced them and so on) while [`InfluenceDirection::Influenced`] walks the descendants.
+///
+/// The graph is walked breadth first, one level per query, and every user is expanded once. Edges
+/// leading back to users that are closer to the root are left out, so cycles are never followed.
+/// Influences hidden by their influencer and the influences `viewer_id` is not allowed to see are
+/// not followed either. At most `max_edges` edges are returned, closest ones first.
+pub async fn get_influence_graph(
+    user_id: i64,
+    direction: InfluenceDirection,
+    max_depth: i32,
+    max_edges: i64,
+    viewer_id: Option<i64>,
+    db: &PgPool,
+) -> Result<InfluenceGraph, InfluenceError> {
+    let max_edges = max_edges.max(0) as usize;
+    // Depth of a user is the level they are first reached on.
+    let mut depths = HashMap::from([(user_id, 0)]);
+    let mut edges = Vec::new();
+    let mut frontier = vec![user_id];
+
+    for depth in 1..=max_depth {
+        if frontier.is_empty() || edges.len() >= max_edges {
+            break;
+        }
+
+        let level_edges = match direction {
+            InfluenceDirection::Influencers => {
+                sqlx::query_as!(
+                    InfluenceEdge,
+                    r#"
+                SELECT from_id, to_id, influence_level, $2::INT4 as "depth!"
+                FROM influences
+                WHERE to_id = ANY($1)
+                    AND acknowledgement <> 'hidden'
+                    AND deleted_at IS NULL
+                    AND influence_visible_to(from_id, to_id, $3)
+                ORDER BY from_id, to_id"#,
+                    &frontier,
+                    depth,
+                    viewer_id,
+                )
+                .fetch_all(db)
+                .await?
+            }
+            InfluenceDirection::Influenced => {
+                sqlx::query_as!(
+                    InfluenceEdge,
+                    r#"
+                SELECT from_id, to_id, influence_level, $2::INT4 as "depth!"
+                FROM influences
+                WHERE from_id = ANY($1)
+                    AND acknowledgement <> 'hidden'
+                    AND deleted_at IS NULL
+                    AND influence_visible_to(from_id, to_id, $3)
+                ORDER BY from_id, to_id"#,
+                    &frontier,
+                    depth,
+                    viewer_id,
+                )
+                .fetch_all(db)
+                .await?
+            }
+        };
+
+        let mut next_frontier = Vec::new();
+        for edge in level_edges {
+            if edges.len() >= max_edges {
+                break;
+            }
+
+            let reached_id = match direction {
+                InfluenceDirection::Influencers => edge.from_id,
+                InfluenceDirection::Influenced => edge.to_id,
+            };
+            match depths.get(&reached_id) {
+                // Leads back towards the root
+                Some(&reached_depth) if reached_depth < depth - 1 => continue,
+                Some(_) => {}
+                None => {
+                    depths.insert(reached_id, depth);
+                    if next_frontier.len() < INFLUENCE_GRAPH_MAX_FRONTIER {
+                        next_frontier.push(reached_id);
+                    }
+                }
+            }
+            edges.push(edge);
+        }
+        frontier = next_frontier;
+    }
+
+    let user_ids: Vec<i64> = depths.keys().copied().collect();
+    let mut nodes = sqlx::query!(
+        "SELECT id, user_name, profile_picture FROM users WHERE id = ANY($1)",
+        &user_ids
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|user| InfluenceNode {
+        depth: depths[&user.id],
+        id: user.id,
+        user_name: user.user_name,
+        profile_picture: user.profile_picture,
+    })
+    .collect::<Vec<_>>();
+    nodes.sort_by_key(|node| (node.depth, node.id));
+
+    Ok(InfluenceGraph {
+        root_id: user_id,
+        nodes,
+        edges,
+    })
+}
+
+/// A chain of influences connecting two users.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct InfluenceChain {
+    /// Ids of the users on the chain, starting with the first influencer and ending with the last
+    /// influenced user
+    pub user_ids: Vec<i64>,
+    /// Influence edges between consecutive users. Depth of an edge is its position on the chain
+    pub edges: Vec<InfluenceEdge>,
+    /// Sum of the influence levels of every edge on the chain
+    pub total_level: i64,
+}
+
+impl InfluenceChain {
+    fn from_path(user_ids: Vec<i64>, levels: Vec<i32>) -> Self {
+        let edges: Vec<InfluenceEdge> = user_ids
+            .windows(2)
+            .zip(levels)
+            .enumerate()
+            .map(|(index, (pair, influence_level))| InfluenceEdge {
+                from_id: pair[0],
+                to_id: pair[1],
+                influence_level,
+                depth: index as i32 + 1,
+            })
+            .collect();
+        let total_level = edges.iter().map(|edge| edge.influence_level as i64).sum();
+
+        Self {
+            user_ids,
+            edges,
+            total_level,
+        }
+    }
+}
+
+/// Finds the shortest chain of influences where `from_id` influenced someone who influenced
+/// someone else and so on, until `to_id` is reached.
+///
+/// The graph is walked breadth first, one hop per query. Each hop looks up the outgoing edges of
+/// the current frontier, which is served by the `influences` primary key. Returns `None` if the
+/// users are not connected within `max_depth` hops through influences `viewer_id` can see.
+pub async fn get_shortest_influence_chain(
+    from_id: i64,
+    to_id: i64,
+    max_depth: i32,
+    viewer_id: Option<i64>,
+    db: &PgPool,
+) -> Result<Option<InfluenceChain>, InfluenceError> {
+    if from_id == to_id {
+        return Ok(Some(InfluenceChain::from_path(vec![from_id], vec![])));
+    }
+
+    // Maps every reached user to the user and influence level it was reached from.
+    let mut parents: HashMap<i64, (i64, i32)> = HashMap::new();
+    let mut frontier = vec![from_id];
+
+    for _ in 0..max_depth {
+        if frontier.is_empty() {
+            break;
+        }
+
+        let edges = sqlx::query!(
+            "SELECT from_id, to_id, influence_level FROM influences WHERE from_id = ANY($1) AND \
+             acknowledgement <> 'hidden' AND deleted_at IS NULL AND influence_visible_to(from_id, \
+             to_id, $2)",
+            &frontier,
+            viewer_id,
+        )
+        .fetch_all(db)
+        .await?;
+
+        let mut next_frontier = Vec::new();
+        for edge in edges {
+            if edge.to_id == from_id || parents.contains_key(&edge.to_id) {
+                continue;
+            }
+            parents.insert(edge.to_id, (edge.from_id, edge.influence_level));
+
+            if edge.to_id == to_id {
+                let mut user_ids = vec![to_id];
+                let mut levels = Vec::new();
+                let mut current = to_id;
+                while let Some(&(parent, level)) = parents.get(&current) {
+                    user_ids.push(parent);
+                    levels.push(level);
+                    current = parent;
+                }
+                user_ids.reverse();
+                levels.reverse();
+
+                return Ok(Some(InfluenceChain::from_path(user_ids, levels)));
+            }
+
+            next_frontier.push(edge.to_id);
+        }
+        frontier = next_frontier;
+    }
+
+    Ok(None)
+}
+
+/// Maximum number of partial chains carried from one hop of the strongest chain search to the
+/// next. Only the strongest ones are kept, so the work doesn't grow with the number of paths.
+const STRONGEST_CHAIN_MAX_PATHS: usize = 200;
+
+/// A chain of the strongest chain search that hasn't reached its last user yet.
+struct PartialChain {
+    user_ids: Vec<i64>,
+    levels: Vec<i32>,
+    total_level: i64,
+}
+
+impl PartialChain {
+    fn last_id(&self) -> i64 {
+        self.user_ids[self.user_ids.len() - 1]
+    }
+
+    fn extend(&self, user_id: i64, influence_level: i32) -> Self {
+        let mut user_ids = self.user_ids.clone();
+        user_ids.push(user_id);
+        let mut levels = self.levels.clone();
+        levels.push(influence_level);
+
+        Self {
+            user_ids,
+            levels,
+            total_level: self.total_level + i64::from(influence_level),
+        }
+    }
+}
+
+/// Finds the chain of influences from `from_id` to `to_id` with the highest summed influence
+/// level. Ties are broken in favour of shorter chains.
+///
+/// The graph is first walked backwards from `to_id` to find the users that can still reach it.
+/// Chains are then extended one hop per query, and only the [`STRONGEST_CHAIN_MAX_PATHS`]
+/// strongest partial chains are carried to the next hop, so dense graphs can't blow up the search.
+/// A chain with weak first hops may be missed because of it. Influences `viewer_id` can't see are
+/// never part of the chain.
+pub async fn get_strongest_influence_chain(
+    from_id: i64,
+    to_id: i64,
+    max_depth: i32,
+    viewer_id: Option<i64>,
+    db: &PgPool,
+) -> Result<Option<InfluenceChain>, InfluenceError> {
+    // Maps every user that can reach `to_id` to the number of hops it needs.
+    let mut distances = HashMap::from([(to_id, 0)]);
+    let mut frontier = vec![to_id];
+
+    for distance in 1..max_depth {
+        if frontier.is_empty() {
+            break;
+        }
+
+        let reached_ids = sqlx::query_scalar!(
+            "SELECT from_id FROM influences WHERE to_id = ANY($1) AND acknowledgement <> 'hidden' \
+             AND deleted_at IS NULL AND influence_visible_to(from_id, to_id, $2)",
+            &frontier,
+            viewer_id,
+        )
+        .fetch_all(db)
+        .await?;
+
+        let mut next_frontier = Vec::new();
+        for reached_id in reached_ids {
+            if let Entry::Vacant(entry) = distances.entry(reached_id) {
+                entry.insert(distance);
+                next_frontier.push(reached_id);
+            }
+        }
+        frontier = next_frontier;
+    }
+
+    let mut chains = vec![PartialChain {
+        user_ids: vec![from_id],
+        levels: Vec::new(),
+        total_level: 0,
+    }];
+    let mut strongest: Option<PartialChain> = None;
+
+    for depth in 1..=max_depth {
+        if chains.is_empty() {
+            break;
+        }
+
+        let last_ids: Vec<i64> = chains.iter().map(PartialChain::last_id).collect();
+        let edges = sqlx::query!(
+            "SELECT from_id, to_id, influence_level FROM influences WHERE from_id = ANY($1) AND \
+             acknowledgement <> 'hidden' AND deleted_at IS NULL AND influence_visible_to(from_id, \
+             to_id, $2)",
+            &last_ids,
+            viewer_id,
+        )
+        .fetch_all(db)
+        .await?;
+
+        let mut edges_by_user: HashMap<i64, Vec<(i64, i32)>> = HashMap::new();
+        for edge in edges {
+            edges_by_user
+                .entry(edge.from_id)
+                .or_default()
+                .push((edge.to_id, edge.influence_level));
+        }
+
+        let hops_left = max_depth - depth;
+        let mut next_chains = Vec::new();
+        for chain in &chains {
+            let Some(edges) = edges_by_user.get(&chain.last_id()) else {
+                continue;
+            };
+
+            for &(next_id, influence_level) in edges {
+                let reachable =
+                    matches!(distances.get(&next_id), Some(&distance) if distance <= hops_left);
+                if !reachable || chain.user_ids.contains(&next_id) {
+                    continue;
+                }
+
+                let extended = chain.extend(next_id, influence_level);
+                if next_id != to_id {
+                    next_chains.push(extended);
+                } else if strongest
+                    .as_ref()
+                    .map_or(true, |best| extended.total_level > best.total_level)
+                {
+                    // Chains found on earlier hops are shorter, so they win ties
+                    strongest = Some(extended);
+                }
+            }
+        }
+
+        next_chains.sort_by(|a, b| b.total_level.cmp(&a.total_level));
+        next_chains.truncate(STRONGEST_CHAIN_MAX_PATHS);
+        chains = next_chains;
+    }
+
+    Ok(strongest.map(|chain| InfluenceChain::from_path(chain.user_ids, chain.levels)))
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::influence::{insert_influence, Influence};
+    use crate::user::{init_user, update_privacy_settings, PrivacySettings, User};
+
+    fn user_for_test(user_id: i64) -> User {
+        User::new(
+            user_id,
+            "boraarslan".to_string(),
+            "random.imageservice.com/boraarslan.jpg".to_string(),
+        )
+    }
+
+    #[sqlx::test]
+    async fn test_influence_graph(db: PgPool) {
+        for user_id in 1..=4 {
+            init_user(user_for_test(user_id), &db).await.unwrap();
+        }
+        // 4 -> 3 -> 2 -> 1 and a cycle back from 1 to 3
+        for (from_id, to_id) in [(2, 1), (3, 2), (4, 3), (1, 3)] {
+            insert_influence(Influence::new(from_id, to_id, 1, None), &db)
+                .await
+                .unwrap();
+        }
+
+        let graph = get_influence_graph(1, InfluenceDirection::Influencers, 2, 100, None, &db)
+            .await
+            .unwrap();
+
+        assert_eq!(graph.edges.len(), 2);
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.nodes[0].id, 1);
+        assert_eq!(graph.nodes[2].id, 3);
+        assert_eq!(graph.nodes[2].depth, 2);
+
+        let graph = get_influence_graph(1, InfluenceDirection::Influencers, 10, 100, None, &db)
+            .await
+            .unwrap();
+
+        // Cycle edge 1 -> 3 is not followed back to the root
+        assert_eq!(graph.edges.len(), 3);
+        assert_eq!(graph.nodes.len(), 4);
+
+        let graph = get_influence_graph(4, InfluenceDirection::Influenced, 10, 100, None, &db)
+            .await
+            .unwrap();
+
+        assert_eq!(graph.edges.len(), 3);
+        assert_eq!(graph.nodes.len(), 4);
+    }
+
+    #[sqlx::test]
+    async fn test_influence_graph_shared_ancestors(db: PgPool) {
+        for user_id in 1..=4 {
+            init_user(user_for_test(user_id), &db).await.unwrap();
+        }
+        // 4 influenced both 2 and 3, who both influenced 1
+        for (from_id, to_id) in [(2, 1), (3, 1), (4, 2), (4, 3)] {
+            insert_influence(Influence::new(from_id, to_id, 1, None), &db)
+                .await
+                .unwrap();
+        }
+
+        let graph = get_influence_graph(1, InfluenceDirection::Influencers, 6, 100, None, &db)
+            .await
+            .unwrap();
+
+        // Both edges of 4 are returned, but 4 is only reached once
+        assert_eq!(graph.edges.len(), 4);
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.nodes[3].id, 4);
+        assert_eq!(graph.nodes[3].depth, 2);
+
+        let graph = get_influence_graph(1, InfluenceDirection::Influencers, 6, 3, None, &db)
+            .await
+            .unwrap();
+
+        assert_eq!(graph.edges.len(), 3);
+        assert_eq!(graph.edges[2].depth, 2);
+    }
+
+    #[sqlx::test]
+    async fn test_influence_chains(db: PgPool) {
+        for user_id in 1..=4 {
+            init_user(user_for_test(user_id), &db).await.unwrap();
+        }
+        // 1 -> 4 directly with a weak influence, 1 -> 2 -> 3 -> 4 with strong influences
+        for (from_id, to_id, level) in [(1, 4, 1), (1, 2, 9), (2, 3, 9), (3, 4, 9)] {
+            insert_influence(Influence::new(from_id, to_id, level, None), &db)
+                .await
+                .unwrap();
+        }
+
+        let shortest = get_shortest_influence_chain(1, 4, 6, None, &db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(shortest.user_ids, vec![1, 4]);
+        assert_eq!(shortest.total_level, 1);
+
+        let strongest = get_strongest_influence_chain(1, 4, 4, None, &db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(strongest.user_ids, vec![1, 2, 3, 4]);
+        assert_eq!(strongest.total_level, 27);
+        assert_eq!(strongest.edges[2].depth, 3);
+
+        let missing = get_shortest_influence_chain(4, 1, 6, None, &db)
+            .await
+            .unwrap();
+        assert!(missing.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_strongest_influence_chain_dense_graph(db: PgPool) {
+        // 1 -> every user of layer 2..=21 -> every user of 22..=41 -> every user of 42..=61 -> 62,
+        // which has 8000 paths. Only 1 -> 5 -> 30 -> 50 -> 62 is made of strong influences
+        let layers = [
+            vec![1],
+            (2..=21).collect(),
+            (22..=41).collect(),
+            (42..=61).collect(),
+            vec![62],
+        ];
+        let strong_path = [1, 5, 30, 50, 62];
+        for user_id in 1..=62 {
+            init_user(user_for_test(user_id), &db).await.unwrap();
+        }
+        for pair in layers.windows(2) {
+            for &from_id in &pair[0] {
+                for &to_id in &pair[1] {
+                    let strong = strong_path.windows(2).any(|edge| edge == [from_id, to_id]);
+                    let level = if strong { 5 } else { 1 };
+                    insert_influence(Influence::new(from_id, to_id, level, None), &db)
+                        .await
+                        .unwrap();
+                }
+            }
+        }
+        // Edges going back to an earlier layer only add cycles
+        for (from_id, to_id) in [(50, 30), (30, 5), (21, 1)] {
+            insert_influence(Influence::new(from_id, to_id, 5, None), &db)
+                .await
+                .unwrap();
+        }
+
+        let strongest = get_strongest_influence_chain(1, 62, 4, None, &db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(strongest.user_ids, strong_path);
+        assert_eq!(strongest.total_level, 20);
+
+        let too_short = get_strongest_influence_chain(1, 62, 3, None, &db)
+            .await
+            .unwrap();
+        assert!(too_short.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_influence_graph_visibility(db: PgPool) {
+        for user_id in 1..=4 {
+            init_user(user_for_test(user_id), &db).await.unwrap();
+        }
+        // 1 -> 2 -> 3 -> 4
+        for (from_id, to_id) in [(1, 2), (2, 3), (3, 4)] {
+            insert_influence(Influence::new(from_id, to_id, 1, None), &db)
+                .await
+                .unwrap();
+        }
+
+        let hide_influences = PrivacySettings {
+            hide_influences: true,
+            ..Default::default()
+        };
+        update_privacy_settings(3, hide_influences, &db)
+            .await
+            .unwrap();
+
+        // Influence list of 3 is only walked by its owner
+        let graph = get_influence_graph(4, InfluenceDirection::Influencers, 6, 100, None, &db)
+            .await
+            .unwrap();
+        assert_eq!(graph.edges.len(), 1);
+
+        let graph = get_influence_graph(4, InfluenceDirection::Influencers, 6, 100, Some(3), &db)
+            .await
+            .unwrap();
+        assert_eq!(graph.edges.len(), 3);
+
+        let chain = get_shortest_influence_chain(1, 4, 6, None, &db)
+            .await
+            .unwrap();
+        assert!(chain.is_none());
+        let chain = get_strongest_influence_chain(1, 4, 4, Some(4), &db)
+            .await
+            .unwrap();
+        assert!(chain.is_none());
+        let chain = get_shortest_influence_chain(1, 4, 6, Some(3), &db)
+            .await
+            .unwrap();
+        assert!(chain.is_some());
+
+        let hide_profile = PrivacySettings {
+            hide_profile: true,
+            ..Default::default()
+        };
+        update_privacy_settings(2, hide_profile, &db).await.unwrap();
+
+        // Hidden profiles are skipped for requests that are not logged in
+        let graph = get_influence_graph(1, InfluenceDirection::Influenced, 6, 100, None, &db)
+            .await
+            .unwrap();
+        assert!(graph.edges.is_empty());
+
+        let graph = get_influence_graph(1, InfluenceDirection::Influenced, 6, 100, Some(4), &db)
+            .await
+            .unwrap();
+        assert_eq!(graph.edges.len(), 1);
+    }
+}
@@ -0,0 +1,190 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+
+use crate::{InfluenceDirection, InfluenceError};
+
+/// Type of change recorded on `influence_history`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[sqlx(type_name = "influence_action", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum InfluenceAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+/// A single change made to an influence.
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InfluenceHistory {
+    pub id: i64,
+    /// Id of the influencer user
+    pub from_id: i64,
+    /// Id of the influenced user
+    pub to_id: i64,
+    pub action: InfluenceAction,
+    /// Id of the user that made the change
+    pub actor_id: i64,
+    /// Influence level before the change. Null for creations and restorations
+    pub old_influence_level: Option<i32>,
+    /// Influence level after the change. Null for deletions
+    pub new_influence_level: Option<i32>,
+    /// Influence info before the change
+    pub old_info: Option<String>,
+    /// Influence info after the change
+    pub new_info: Option<String>,
+    /// Influence tags before the change. Null if the change didn't touch the tags
+    pub old_tags: Option<Vec<String>>,
+    /// Influence tags after the change. Null if the change didn't touch the tags
+    pub new_tags: Option<Vec<String>>,
+    /// Date of the change
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+/// Returns the influence history of a user, newest changes first.
+///
+/// [`InfluenceDirection::Influencers`] returns the changes the user made to their own influence
+/// list, [`InfluenceDirection::Influenced`] returns the changes other users made about them.
+/// Changes to the influences `viewer_id` is not allowed to see are left out.
+pub async fn get_influence_history(
+    user_id: i64,
+    direction: InfluenceDirection,
+    viewer_id: Option<i64>,
+    limit: i64,
+    offset: i64,
+    db: &PgPool,
+) -> Result<Vec<InfluenceHistory>, InfluenceError> {
+    let search_result = match direction {
+        InfluenceDirection::Influencers => {
+            sqlx::query_as!(
+                InfluenceHistory,
+                r#"
+                SELECT
+                    id, from_id, to_id, action as "action: InfluenceAction", actor_id,
+                    old_influence_level, new_influence_level, old_info, new_info, old_tags, new_tags,
+                    created_at
+                FROM influence_history
+                WHERE to_id = $1 AND influence_visible_to(from_id, to_id, $2)
+                ORDER BY created_at DESC, id DESC
+                LIMIT $3 OFFSET $4"#,
+                user_id,
+                viewer_id,
+                limit,
+                offset,
+            )
+            .fetch_all(db)
+            .await
+        }
+        InfluenceDirection::Influenced => {
+            sqlx::query_as!(
+                InfluenceHistory,
+                r#"
+                SELECT
+                    id, from_id, to_id, action as "action: InfluenceAction", actor_id,
+                    old_influence_level, new_influence_level, old_info, new_info, old_tags, new_tags,
+                    created_at
+                FROM influence_history
+                WHERE from_id = $1 AND influence_visible_to(from_id, to_id, $2)
+                ORDER BY created_at DESC, id DESC
+                LIMIT $3 OFFSET $4"#,
+                user_id,
+                viewer_id,
+                limit,
+                offset,
+            )
+            .fetch_all(db)
+            .await
+        }
+    };
+
+    match search_result {
+        Ok(history) => Ok(history),
+        Err(db_err) => Err(InfluenceError::from(db_err)),
+    }
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::influence::{
+        delete_influence, insert_influence, update_influence_info, update_influence_level,
+        Influence,
+    };
+    use crate::user::{init_user, update_privacy_settings, PrivacySettings, User};
+
+    fn user_for_test(user_id: i64) -> User {
+        User::new(
+            user_id,
+            "boraarslan".to_string(),
+            "random.imageservice.com/boraarslan.jpg".to_string(),
+        )
+    }
+
+    #[sqlx::test]
+    async fn test_influence_history(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+
+        insert_influence(Influence::new(1, 2, 3, None), &db)
+            .await
+            .unwrap();
+        update_influence_level(1, 2, 5, 2, &db).await.unwrap();
+        update_influence_info(1, 2, Some("Some info"), None, 2, &db)
+            .await
+            .unwrap();
+        delete_influence(1, 2, 2, &db).await.unwrap();
+
+        let history = get_influence_history(2, InfluenceDirection::Influencers, None, 10, 0, &db)
+            .await
+            .unwrap();
+
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0].action, InfluenceAction::Delete);
+        assert_eq!(history[0].old_info, Some("Some info".to_string()));
+        assert_eq!(history[2].action, InfluenceAction::Update);
+        assert_eq!(history[2].old_influence_level, Some(3));
+        assert_eq!(history[2].new_influence_level, Some(5));
+        assert_eq!(history[3].action, InfluenceAction::Create);
+        assert_eq!(history[3].actor_id, 2);
+
+        let history = get_influence_history(1, InfluenceDirection::Influenced, None, 10, 0, &db)
+            .await
+            .unwrap();
+
+        assert_eq!(history.len(), 4);
+
+        let history = get_influence_history(1, InfluenceDirection::Influencers, None, 10, 0, &db)
+            .await
+            .unwrap();
+
+        assert!(history.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_influence_history_visibility(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+
+        insert_influence(Influence::new(1, 2, 3, None), &db)
+            .await
+            .unwrap();
+
+        let settings = PrivacySettings {
+            hide_influences: true,
+            ..Default::default()
+        };
+        update_privacy_settings(2, settings, &db).await.unwrap();
+
+        for (viewer_id, visible) in [(None, 0), (Some(1), 0), (Some(2), 1)] {
+            let history =
+                get_influence_history(1, InfluenceDirection::Influenced, viewer_id, 10, 0, &db)
+                    .await
+                    .unwrap();
+            assert_eq!(history.len(), visible);
+        }
+    }
+}
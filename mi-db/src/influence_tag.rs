@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+
+use crate::InfluenceError;
+
+/// A tag from the influence tag vocabulary, such as rhythm or hitsounding.
+#[derive(Debug, FromRow, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct InfluenceTag {
+    /// Name of the tag. Used to refer to the tag in requests
+    pub name: String,
+    /// Short explanation of what the tag covers
+    pub description: String,
+}
+
+/// Returns the whole tag vocabulary.
+pub async fn get_influence_tags(db: &PgPool) -> Result<Vec<InfluenceTag>, InfluenceError> {
+    let search_result = sqlx::query_as!(
+        InfluenceTag,
+        "SELECT name, description FROM influence_tags ORDER BY id"
+    )
+    .fetch_all(db)
+    .await;
+
+    match search_result {
+        Ok(tags) => Ok(tags),
+        Err(db_err) => Err(InfluenceError::from(db_err)),
+    }
+}
+
+/// Replaces the tags of an influence inside the given transaction. The influence must exist.
+pub(crate) async fn replace_influence_tags(
+    from_id: i64,
+    to_id: i64,
+    tags: &[String],
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), InfluenceError> {
+    let known_tags = sqlx::query!(
+        "SELECT id, name FROM influence_tags WHERE name = ANY($1)",
+        tags
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    let unknown_tags: Vec<String> = tags
+        .iter()
+        .filter(|tag| !known_tags.iter().any(|known| &known.name == *tag))
+        .cloned()
+        .collect();
+    if !unknown_tags.is_empty() {
+        return Err(InfluenceError::UnknownInfluenceTags(unknown_tags));
+    }
+
+    sqlx::query!(
+        "DELETE FROM influence_tag_links WHERE from_id = $1 AND to_id = $2",
+        from_id,
+        to_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    let tag_ids: Vec<i32> = known_tags.iter().map(|tag| tag.id).collect();
+    sqlx::query!(
+        "INSERT INTO influence_tag_links (from_id, to_id, tag_id) SELECT $1, $2, UNNEST($3::INT[])",
+        from_id,
+        to_id,
+        &tag_ids
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::influence::{
+        get_all_influences_by_to_id, get_influences_page, insert_influence, update_influence_info,
+        Influence, InfluenceListOptions,
+    };
+    use crate::influence_history::get_influence_history;
+    use crate::user::{init_user, User};
+    use crate::InfluenceDirection;
+
+    fn user_for_test(user_id: i64) -> User {
+        User::new(
+            user_id,
+            "boraarslan".to_string(),
+            "random.imageservice.com/boraarslan.jpg".to_string(),
+        )
+    }
+
+    #[sqlx::test]
+    async fn test_influence_tags(db: PgPool) {
+        let vocabulary = get_influence_tags(&db).await.unwrap();
+        assert!(vocabulary.iter().any(|tag| tag.name == "rhythm"));
+
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+        init_user(user_for_test(3), &db).await.unwrap();
+
+        let influence = Influence::new(1, 3, 5, None).with_tags(vec!["rhythm".to_string()]);
+        insert_influence(influence, &db).await.unwrap();
+        insert_influence(Influence::new(2, 3, 5, None), &db)
+            .await
+            .unwrap();
+
+        update_influence_info(
+            2,
+            3,
+            Some("Some info"),
+            Some(&["hitsounding".to_string(), "patterning".to_string()][..]),
+            3,
+            &db,
+        )
+        .await
+        .unwrap();
+
+        let error = update_influence_info(2, 3, None, Some(&["not a tag".to_string()][..]), 3, &db)
+            .await
+            .unwrap_err();
+        match error {
+            InfluenceError::UnknownInfluenceTags(tags) => assert_eq!(tags, vec!["not a tag"]),
+            _ => panic!("Should return unknown tags error."),
+        }
+
+        let influences = get_all_influences_by_to_id(3, &db).await.unwrap();
+        let second = influences.iter().find(|i| i.from_id == 2).unwrap();
+        assert_eq!(second.tags.len(), 2);
+        // The rejected update leaves the info untouched as well
+        assert_eq!(second.info, Some("Some info".to_string()));
+
+        let history = get_influence_history(3, InfluenceDirection::Influencers, None, 10, 0, &db)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].old_tags, Some(vec![]));
+        assert_eq!(
+            history[0].new_tags,
+            Some(vec!["patterning".to_string(), "hitsounding".to_string()])
+        );
+
+        let options = InfluenceListOptions {
+            tag: Some("rhythm".to_string()),
+            ..Default::default()
+        };
+        let page = get_influences_page(3, options, &db).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].from_id, 1);
+        assert_eq!(page[0].tags, vec!["rhythm"]);
+    }
+}
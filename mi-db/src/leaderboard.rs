@@ -1,4 +1,6 @@
+use mi_osu_api::UserGroup;
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use sqlx::PgPool;
 use utoipa::ToSchema;
 
@@ -9,14 +11,27 @@ pub struct LeaderboardUser {
     pub profile_picture: String,
     pub ranked_map_count: i32,
     pub influence_count: Option<i64>,
+    /// 2 digit ISO country code
+    pub country_code: Option<String>,
+    /// Groups the user is part of, such as BN or NAT
+    #[schema(value_type = Vec<UserGroup>)]
+    pub groups: Json<Vec<UserGroup>>,
+    /// Profile title of the user
+    pub title: Option<String>,
 }
 
+/// Returns the users that are listed as an influence the most. Users that hide their profile or
+/// don't allow being listed are left out, and only influences anonymous users can see are counted.
 pub async fn get_user_leaderboard(db: &PgPool) -> Result<Vec<LeaderboardUser>, sqlx::Error> {
     sqlx::query_as!(
         LeaderboardUser,
-        "WITH top_influencers AS (
+        r#"WITH top_influencers AS (
             SELECT from_id, COUNT(*) AS influence_count
             FROM influences
+            WHERE acknowledgement <> 'hidden' AND deleted_at IS NULL AND NOT EXISTS (
+                SELECT 1 FROM user_profiles p
+                WHERE p.user_id = from_id AND NOT p.allow_influence_listing
+            ) AND influence_visible_to(from_id, to_id, NULL)
             GROUP BY from_id
             ORDER BY influence_count DESC
             LIMIT 20
@@ -26,11 +41,74 @@ pub async fn get_user_leaderboard(db: &PgPool) -> Result<Vec<LeaderboardUser>, s
             users.user_name,
             users.profile_picture,
             users_osu_data.ranked_count as ranked_map_count,
-            top_influencers.influence_count
+            top_influencers.influence_count,
+            users_osu_data.country_code,
+            users_osu_data.groups as "groups: Json<Vec<UserGroup>>",
+            users_osu_data.title
         FROM top_influencers
         INNER JOIN users ON id = from_id
-        INNER JOIN users_osu_data ON users_osu_data.user_id = from_id"
+        INNER JOIN users_osu_data ON users_osu_data.user_id = from_id"#
     )
     .fetch_all(db)
     .await
 }
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::influence::{insert_influence, Influence};
+    use crate::user::{init_user, update_privacy_settings, PrivacySettings, User};
+
+    fn user_for_test(user_id: i64) -> User {
+        User::new(
+            user_id,
+            "boraarslan".to_string(),
+            "random.imageservice.com/boraarslan.jpg".to_string(),
+        )
+    }
+
+    #[sqlx::test]
+    async fn test_user_leaderboard_privacy(db: PgPool) {
+        for user_id in 1..=6 {
+            init_user(user_for_test(user_id), &db).await.unwrap();
+        }
+        // 1 is listed by 2, 3, 4 and 5, 6 is listed by 2
+        for (from_id, to_id) in [(1, 2), (1, 3), (1, 4), (1, 5), (6, 2)] {
+            insert_influence(Influence::new(from_id, to_id, 1, None), &db)
+                .await
+                .unwrap();
+        }
+
+        let leaderboard = get_user_leaderboard(&db).await.unwrap();
+        assert_eq!(leaderboard.len(), 2);
+
+        let hide_influences = PrivacySettings {
+            hide_influences: true,
+            ..Default::default()
+        };
+        update_privacy_settings(2, hide_influences, &db)
+            .await
+            .unwrap();
+        let hide_profile = PrivacySettings {
+            hide_profile: true,
+            ..Default::default()
+        };
+        update_privacy_settings(3, hide_profile, &db).await.unwrap();
+
+        let leaderboard = get_user_leaderboard(&db).await.unwrap();
+        assert_eq!(leaderboard.len(), 1);
+        assert_eq!(leaderboard[0].id, 1);
+        assert_eq!(leaderboard[0].influence_count, Some(2));
+
+        let hide_profile = PrivacySettings {
+            hide_profile: true,
+            ..Default::default()
+        };
+        update_privacy_settings(1, hide_profile, &db).await.unwrap();
+
+        let leaderboard = get_user_leaderboard(&db).await.unwrap();
+        assert!(leaderboard.is_empty());
+    }
+}
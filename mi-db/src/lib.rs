@@ -1,16 +1,28 @@
+pub mod api_key;
 pub mod auth;
 pub mod influence;
+pub mod influence_beatmap;
+pub mod influence_graph;
+pub mod influence_history;
+pub mod influence_tag;
 pub mod leaderboard;
 pub mod user;
+pub mod user_data;
 pub mod user_lock;
 
 use bb8::Pool;
 use bb8_redis::RedisConnectionManager;
 
+pub use crate::api_key::*;
 pub use crate::auth::*;
 pub use crate::influence::*;
+pub use crate::influence_beatmap::*;
+pub use crate::influence_graph::*;
+pub use crate::influence_history::*;
+pub use crate::influence_tag::*;
 pub use crate::leaderboard::*;
 pub use crate::user::*;
+pub use crate::user_data::*;
 pub use crate::user_lock::*;
 
 pub type RedisPool = Pool<RedisConnectionManager>;
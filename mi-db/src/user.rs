@@ -1,7 +1,7 @@
 use chrono::Utc;
 use mi_core::error::{AppErrorExt, ErrorType};
 use mi_core::INTERNAL_DB_ERROR_MESSAGE;
-use mi_osu_api::Beatmapset;
+use mi_osu_api::{Beatmapset, UserGroup};
 use serde::{Deserialize, Serialize};
 use sqlx::types::Json;
 use sqlx::{FromRow, PgPool};
@@ -11,6 +11,12 @@ use utoipa::ToSchema;
 
 use crate::PG_UNIQUE_KEY_VIOLATION;
 
+/// Hours after which the stored mapsets of a user are refreshed from osu!.
+pub const USER_MAPSETS_STALE_HOURS: i64 = 24;
+
+/// Maximum number of featured maps a user can have.
+pub const FEATURED_MAPS_LIMIT: usize = 5;
+
 #[derive(Debug, FromRow, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
 pub struct User {
     /// Osu user ID of a user
@@ -56,6 +62,81 @@ pub struct Maps {
     pub featured_map_id: i64,
 }
 
+/// A name the user had before changing it on osu!.
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UserNameHistory {
+    /// Former name of the user
+    pub user_name: String,
+    /// Date the user stopped using this name
+    pub changed_at: chrono::DateTime<Utc>,
+}
+
+/// Site an external profile link points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SocialPlatform {
+    Twitter,
+    Youtube,
+    Twitch,
+    Discord,
+    /// Personal site of the user. Can be on any host
+    Website,
+}
+
+impl SocialPlatform {
+    /// Hosts links of this platform can point to. `None` if any host is allowed.
+    pub fn allowed_hosts(&self) -> Option<&'static [&'static str]> {
+        match self {
+            SocialPlatform::Twitter => Some(&["twitter.com", "www.twitter.com", "x.com"]),
+            SocialPlatform::Youtube => Some(&[
+                "youtube.com",
+                "www.youtube.com",
+                "m.youtube.com",
+                "youtu.be",
+            ]),
+            SocialPlatform::Twitch => Some(&["twitch.tv", "www.twitch.tv"]),
+            SocialPlatform::Discord => Some(&["discord.gg", "discord.com", "www.discord.com"]),
+            SocialPlatform::Website => None,
+        }
+    }
+
+    /// Url schemes links of this platform can use.
+    pub fn allowed_schemes(&self) -> &'static [&'static str] {
+        match self {
+            SocialPlatform::Website => &["https", "http"],
+            _ => &["https"],
+        }
+    }
+}
+
+/// An external link on the profile of a user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SocialLink {
+    pub platform: SocialPlatform,
+    pub url: String,
+}
+
+/// Privacy settings of a user.
+#[derive(Debug, FromRow, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PrivacySettings {
+    /// Hide the profile from users that are not logged in
+    pub hide_profile: bool,
+    /// Hide the influence list of the user from everyone but themselves
+    pub hide_influences: bool,
+    /// Allow other users to list the user as an influence
+    pub allow_influence_listing: bool,
+}
+
+impl Default for PrivacySettings {
+    fn default() -> Self {
+        Self {
+            hide_profile: false,
+            hide_influences: false,
+            allow_influence_listing: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserOsuData {
     /// Osu user ID of a user (references user id from `users` table)
@@ -70,6 +151,20 @@ pub struct UserOsuData {
     pub graveyard_count: i32,
     /// Guest map count
     pub guest_count: i32,
+    /// 2 digit ISO country code
+    pub country_code: Option<String>,
+    /// Name of the country
+    pub country_name: Option<String>,
+    /// Groups the user is part of, such as BN or NAT
+    pub groups: Json<Vec<UserGroup>>,
+    /// Profile title of the user
+    pub title: Option<String>,
+    /// Url to profile cover image
+    pub cover_url: Option<String>,
+    /// Count of users that are subscribed to this user's beatmap updates
+    pub followers: i64,
+    /// Main playmode of the user
+    pub playmode: Option<String>,
     // Last modified timestamp. Not used during inserts and defaulted
     pub modified_at: chrono::DateTime<Utc>,
     /// Creation date. Not used during inserts and defaulted. Skipped serialization to not include
@@ -91,6 +186,9 @@ pub struct FullUser {
     // Featured maps of the user
     #[schema(value_type = Option<FeaturedMaps>)]
     pub featured_maps: Option<Json<FeaturedMaps>>,
+    /// External links of the user, in the order they are shown
+    #[schema(value_type = Vec<SocialLink>)]
+    pub social_links: Json<Vec<SocialLink>>,
     /// Ranked map count
     pub ranked_count: i32,
     /// Loved map count
@@ -101,6 +199,21 @@ pub struct FullUser {
     pub graveyard_count: i32,
     /// Guest map count
     pub guest_count: i32,
+    /// 2 digit ISO country code
+    pub country_code: Option<String>,
+    /// Name of the country
+    pub country_name: Option<String>,
+    /// Groups the user is part of, such as BN or NAT
+    #[schema(value_type = Vec<UserGroup>)]
+    pub groups: Json<Vec<UserGroup>>,
+    /// Profile title of the user
+    pub title: Option<String>,
+    /// Url to profile cover image
+    pub cover_url: Option<String>,
+    /// Count of users that are subscribed to this user's beatmap updates
+    pub followers: i64,
+    /// Main playmode of the user
+    pub playmode: Option<String>,
     /// Last osu! data modified timestamp.
     pub osu_data_modified_at: chrono::DateTime<Utc>,
     /// last profile data modified timestamp
@@ -108,10 +221,31 @@ pub struct FullUser {
 }
 
 impl FullUser {
-    pub fn is_outdated(&self) -> bool {
-        let now = Utc::now();
-        let diff = now - self.osu_data_modified_at;
-        diff.num_hours() > 3
+    /// Returns whether the osu! data of the user should be refreshed according to the given rule.
+    pub fn is_outdated(&self, rule: &dyn StalenessRule) -> bool {
+        rule.is_stale(self, Utc::now())
+    }
+}
+
+/// Decides whether the stored osu! data of a user is stale and should be refreshed.
+pub trait StalenessRule: std::fmt::Debug + Send + Sync {
+    fn is_stale(&self, user: &FullUser, now: chrono::DateTime<Utc>) -> bool;
+
+    /// Osu! data that was last stored before the returned date is stale at `now`.
+    fn stale_before(&self, now: chrono::DateTime<Utc>) -> chrono::DateTime<Utc>;
+}
+
+/// Osu! data is stale once it is older than the given age.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxAge(pub chrono::Duration);
+
+impl StalenessRule for MaxAge {
+    fn is_stale(&self, user: &FullUser, now: chrono::DateTime<Utc>) -> bool {
+        now - user.osu_data_modified_at > self.0
+    }
+
+    fn stale_before(&self, now: chrono::DateTime<Utc>) -> chrono::DateTime<Utc> {
+        now - self.0
     }
 }
 
@@ -153,6 +287,41 @@ pub async fn get_user(user_id: i64, db: &PgPool) -> Result<User, UserError> {
     }
 }
 
+/// Searches users whose user name contains the given query, case insensitively. Closest matches
+/// are returned first.
+pub async fn search_users(
+    query: &str,
+    limit: i64,
+    offset: i64,
+    db: &PgPool,
+) -> Result<Vec<User>, UserError> {
+    // Wildcards in the query are matched literally
+    let pattern = format!(
+        "%{}%",
+        query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+
+    let search_result = sqlx::query_as!(
+        User,
+        "SELECT id, user_name, profile_picture, modified_at, created_at FROM users WHERE \
+         user_name ILIKE $1 ORDER BY similarity(user_name, $2) DESC, id LIMIT $3 OFFSET $4",
+        pattern,
+        query,
+        limit,
+        offset,
+    )
+    .fetch_all(db)
+    .await;
+
+    match search_result {
+        Ok(users) => Ok(users),
+        Err(db_err) => Err(UserError::from(db_err)),
+    }
+}
+
 pub async fn get_full_user(user_id: i64, db: &PgPool) -> Result<FullUser, UserError> {
     let search_result = sqlx::query_as!(
         FullUser,
@@ -161,8 +330,11 @@ pub async fn get_full_user(user_id: i64, db: &PgPool) -> Result<FullUser, UserEr
             id, user_name, profile_picture, 
             profile.bio, 
             profile.featured_maps as "featured_maps: Json<FeaturedMaps>", 
+            profile.social_links as "social_links: Json<Vec<SocialLink>>",
             profile.modified_at as profile_data_modified_at,
             osu.ranked_count, osu.loved_count, osu.nominated_count, osu.graveyard_count, osu.guest_count,
+            osu.country_code, osu.country_name, osu.groups as "groups: Json<Vec<UserGroup>>",
+            osu.title, osu.cover_url, osu.followers, osu.playmode,
             osu.modified_at as osu_data_modified_at
         FROM users 
         INNER JOIN user_profiles profile ON profile.user_id = $1 
@@ -178,16 +350,31 @@ pub async fn get_full_user(user_id: i64, db: &PgPool) -> Result<FullUser, UserEr
     }
 }
 
+/// Stores the osu! data of a user. If the user changed their name on osu!, the name is updated as
+/// well and the previous name is recorded on `user_name_history`.
 pub async fn update_user_osu_data(
     user_osu_data: mi_osu_api::User,
     db: &PgPool,
 ) -> Result<(), UserError> {
+    // Custom covers are the ones shown on the profile if the user has set one
+    let cover_url = user_osu_data
+        .cover
+        .custom_url
+        .clone()
+        .or_else(|| user_osu_data.cover.url.clone());
+
+    let mut transaction = db.begin().await?;
+
     let query_result = sqlx::query!(
         r#"
         UPDATE 
             users_osu_data 
-                SET (ranked_count, loved_count, nominated_count, graveyard_count, guest_count, modified_at) = 
-                ($2 , $3, $4, $5, $6, DEFAULT) 
+                SET (
+                    ranked_count, loved_count, nominated_count, graveyard_count, guest_count,
+                    country_code, country_name, groups, title, cover_url, followers, playmode,
+                    modified_at
+                ) = 
+                ($2 , $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, DEFAULT) 
         WHERE 
             user_id = $1 "#,
         user_osu_data.id,
@@ -196,11 +383,96 @@ pub async fn update_user_osu_data(
         user_osu_data.stats.nominated,
         user_osu_data.stats.graveyard,
         user_osu_data.stats.guest,
-    ).execute(db).await;
+        user_osu_data.country.code,
+        user_osu_data.country.name,
+        Json(&user_osu_data.groups) as _,
+        user_osu_data.title,
+        cover_url,
+        user_osu_data.followers,
+        user_osu_data.playmode,
+    )
+    .execute(&mut transaction)
+    .await;
 
     match query_result {
-        Ok(_) => Ok(()),
-        Err(sqlx::Error::RowNotFound) => Err(UserError::UserNotFound(user_osu_data.id)),
+        Ok(_) => {}
+        Err(sqlx::Error::RowNotFound) => return Err(UserError::UserNotFound(user_osu_data.id)),
+        Err(db_err) => return Err(UserError::from(db_err)),
+    }
+
+    sqlx::query!(
+        r#"
+        WITH old AS (
+            SELECT id, user_name FROM users WHERE id = $1 FOR UPDATE
+        ), updated AS (
+            UPDATE users SET (user_name, modified_at) = ($2, DEFAULT)
+            FROM old
+            WHERE users.id = old.id AND old.user_name <> $2
+            RETURNING users.id
+        )
+        INSERT INTO user_name_history (user_id, user_name)
+        SELECT old.id, old.user_name FROM old, updated"#,
+        user_osu_data.id,
+        user_osu_data.username,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Finds a user by their current or any of their former user names, case insensitively.
+///
+/// Current names take precedence. If multiple users had the given name, the user that had it most
+/// recently is returned.
+pub async fn get_user_by_name(user_name: &str, db: &PgPool) -> Result<User, UserError> {
+    let current_user = sqlx::query_as!(
+        User,
+        "SELECT id, user_name, profile_picture, modified_at, created_at FROM users WHERE \
+         LOWER(user_name) = LOWER($1) ORDER BY modified_at DESC LIMIT 1",
+        user_name
+    )
+    .fetch_optional(db)
+    .await?;
+
+    if let Some(user) = current_user {
+        return Ok(user);
+    }
+
+    let former_user = sqlx::query_as!(
+        User,
+        r#"
+        SELECT users.id, users.user_name, users.profile_picture, users.modified_at, users.created_at
+        FROM user_name_history history
+        INNER JOIN users ON users.id = history.user_id
+        WHERE LOWER(history.user_name) = LOWER($1)
+        ORDER BY history.changed_at DESC
+        LIMIT 1"#,
+        user_name
+    )
+    .fetch_optional(db)
+    .await?;
+
+    former_user.ok_or_else(|| UserError::UserNameNotFound(user_name.to_string()))
+}
+
+/// Returns the former names of a user, most recently changed first.
+pub async fn get_user_name_history(
+    user_id: i64,
+    db: &PgPool,
+) -> Result<Vec<UserNameHistory>, UserError> {
+    let search_result = sqlx::query_as!(
+        UserNameHistory,
+        "SELECT user_name, changed_at FROM user_name_history WHERE user_id = $1 ORDER BY \
+         changed_at DESC, id DESC",
+        user_id
+    )
+    .fetch_all(db)
+    .await;
+
+    match search_result {
+        Ok(history) => Ok(history),
         Err(db_err) => Err(UserError::from(db_err)),
     }
 }
@@ -227,6 +499,190 @@ pub async fn update_user_featured_maps(
     }
 }
 
+/// Sets the featured maps of a user in the given order and returns them.
+///
+/// Every featured map has to be a difficulty of one of the user's own beatmapsets that are stored
+/// on `user_osu_maps`.
+pub async fn set_user_featured_maps(
+    user_id: i64,
+    featured_map_ids: &[i64],
+    db: &PgPool,
+) -> Result<FeaturedMaps, UserError> {
+    if featured_map_ids.len() > FEATURED_MAPS_LIMIT {
+        return Err(UserError::TooManyFeaturedMaps(featured_map_ids.len()));
+    }
+
+    let mapsets = get_user_mapsets(user_id, db).await?;
+    let mut maps: Vec<Maps> = Vec::with_capacity(featured_map_ids.len());
+
+    for &featured_map_id in featured_map_ids {
+        if maps
+            .iter()
+            .any(|map| map.featured_map_id == featured_map_id)
+        {
+            return Err(UserError::DuplicateFeaturedMap(featured_map_id));
+        }
+
+        let beatmapset = mapsets
+            .iter()
+            .find(|mapset| mapset.beatmaps.iter().any(|map| map.id == featured_map_id))
+            .ok_or(UserError::FeaturedMapNotOwned(featured_map_id))?;
+
+        maps.push(Maps {
+            beatmapset: beatmapset.clone(),
+            featured_map_id,
+        });
+    }
+
+    let featured_maps = FeaturedMaps { maps };
+    update_user_featured_maps(user_id, featured_maps.clone(), db).await?;
+
+    Ok(featured_maps)
+}
+
+/// Reorders the current featured maps of a user and returns them. `featured_map_ids` must contain
+/// every current featured map exactly once.
+pub async fn reorder_user_featured_maps(
+    user_id: i64,
+    featured_map_ids: &[i64],
+    db: &PgPool,
+) -> Result<FeaturedMaps, UserError> {
+    let profile = sqlx::query!(
+        r#"SELECT featured_maps as "featured_maps: Json<FeaturedMaps>" FROM user_profiles WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(UserError::UserNotFound(user_id))?;
+
+    let mut current_maps = profile
+        .featured_maps
+        .map(|featured_maps| featured_maps.0.maps)
+        .unwrap_or_default();
+
+    if current_maps.len() != featured_map_ids.len() {
+        return Err(UserError::FeaturedMapsMismatch);
+    }
+
+    let mut maps = Vec::with_capacity(featured_map_ids.len());
+    for featured_map_id in featured_map_ids {
+        let index = current_maps
+            .iter()
+            .position(|map| map.featured_map_id == *featured_map_id)
+            .ok_or(UserError::FeaturedMapsMismatch)?;
+        maps.push(current_maps.swap_remove(index));
+    }
+
+    let featured_maps = FeaturedMaps { maps };
+    update_user_featured_maps(user_id, featured_maps.clone(), db).await?;
+
+    Ok(featured_maps)
+}
+
+pub async fn clear_user_featured_maps(user_id: i64, db: &PgPool) -> Result<(), UserError> {
+    let update_result = sqlx::query!(
+        "UPDATE user_profiles SET (featured_maps, modified_at) = (NULL, DEFAULT) WHERE user_id = \
+         $1 RETURNING user_id",
+        user_id,
+    )
+    .fetch_one(db)
+    .await;
+
+    match update_result {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::RowNotFound) => Err(UserError::UserNotFound(user_id)),
+        Err(db_err) => Err(UserError::from(db_err)),
+    }
+}
+
+/// Returns when the mapsets of a user were last stored. `None` if they were never stored.
+pub async fn get_user_mapsets_modified_at(
+    user_id: i64,
+    db: &PgPool,
+) -> Result<Option<chrono::DateTime<Utc>>, UserError> {
+    let result = sqlx::query!(
+        "SELECT modified_at FROM user_osu_maps WHERE user_id = $1",
+        user_id
+    )
+    .fetch_optional(db)
+    .await;
+
+    match result {
+        Ok(row) => Ok(row.map(|row| row.modified_at)),
+        Err(db_err) => Err(UserError::from(db_err)),
+    }
+}
+
+/// Returns the ids of the users whose mapsets were never stored or were stored more than
+/// [`USER_MAPSETS_STALE_HOURS`] hours ago, most outdated first.
+pub async fn get_users_with_stale_mapsets(limit: i64, db: &PgPool) -> Result<Vec<i64>, UserError> {
+    let stale_before = Utc::now() - chrono::Duration::hours(USER_MAPSETS_STALE_HOURS);
+    let result = sqlx::query!(
+        r#"
+        SELECT users.id FROM users
+        LEFT JOIN user_osu_maps maps ON maps.user_id = users.id
+        WHERE maps.modified_at IS NULL OR maps.modified_at < $1
+        ORDER BY maps.modified_at NULLS FIRST, users.id
+        LIMIT $2"#,
+        stale_before,
+        limit
+    )
+    .fetch_all(db)
+    .await;
+
+    match result {
+        Ok(rows) => Ok(rows.into_iter().map(|row| row.id).collect()),
+        Err(db_err) => Err(UserError::from(db_err)),
+    }
+}
+
+/// Returns the ids of the users whose osu! data was last stored before `stale_before`, most
+/// outdated first.
+///
+/// Users whose last refresh attempt failed after `stale_before` are left out, so they don't keep
+/// the rest of the users from being refreshed.
+pub async fn get_users_with_stale_osu_data(
+    stale_before: chrono::DateTime<Utc>,
+    limit: i64,
+    db: &PgPool,
+) -> Result<Vec<i64>, UserError> {
+    let result = sqlx::query!(
+        r#"
+        SELECT user_id FROM users_osu_data
+        WHERE modified_at < $1
+            AND (last_refresh_attempt_at IS NULL OR last_refresh_attempt_at < $1)
+        ORDER BY GREATEST(modified_at, last_refresh_attempt_at), user_id
+        LIMIT $2"#,
+        stale_before,
+        limit
+    )
+    .fetch_all(db)
+    .await;
+
+    match result {
+        Ok(rows) => Ok(rows.into_iter().map(|row| row.user_id).collect()),
+        Err(db_err) => Err(UserError::from(db_err)),
+    }
+}
+
+/// Records a failed refresh of the osu! data of a user. The user is not returned from
+/// [`get_users_with_stale_osu_data`] until the attempt is stale as well.
+pub async fn record_osu_data_refresh_attempt(user_id: i64, db: &PgPool) -> Result<(), UserError> {
+    let update_result = sqlx::query!(
+        "UPDATE users_osu_data SET last_refresh_attempt_at = CURRENT_TIMESTAMP WHERE user_id = $1 \
+         RETURNING user_id",
+        user_id
+    )
+    .fetch_one(db)
+    .await;
+
+    match update_result {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::RowNotFound) => Err(UserError::UserNotFound(user_id)),
+        Err(db_err) => Err(UserError::from(db_err)),
+    }
+}
+
 pub async fn get_user_mapsets(user_id: i64, db: &PgPool) -> Result<Vec<Beatmapset>, UserError> {
     let result = sqlx::query!(
         r#"SELECT mapsets as "mapsets: Json<Vec<Beatmapset>>" FROM user_osu_maps WHERE user_id = $1"#,
@@ -317,6 +773,8 @@ pub async fn init_user(user: User, db: &PgPool) -> Result<User, UserError> {
     Ok(inserted_user)
 }
 
+/// Overwrites the name of a user without recording the former one. Names come from osu!, so
+/// [`update_user_osu_data`] should be used to keep the name history complete.
 pub async fn update_user_name(user_name: &str, user_id: i64, db: &PgPool) -> Result<(), UserError> {
     let update_result = sqlx::query!(
         "UPDATE users SET (user_name, modified_at) = ($1, DEFAULT) WHERE id = $2 RETURNING id",
@@ -354,6 +812,96 @@ pub async fn update_user_picture(
     }
 }
 
+pub async fn get_privacy_settings(user_id: i64, db: &PgPool) -> Result<PrivacySettings, UserError> {
+    let search_result = sqlx::query_as!(
+        PrivacySettings,
+        "SELECT hide_profile, hide_influences, allow_influence_listing FROM user_profiles WHERE \
+         user_id = $1",
+        user_id
+    )
+    .fetch_one(db)
+    .await;
+
+    match search_result {
+        Ok(settings) => Ok(settings),
+        Err(sqlx::Error::RowNotFound) => Err(UserError::UserNotFound(user_id)),
+        Err(db_err) => Err(UserError::from(db_err)),
+    }
+}
+
+/// Checks whether the profile of a user can be seen by the viewer. Hidden profiles can only be seen
+/// by logged in users, `viewer_id` is `None` for requests that are not logged in.
+///
+/// Users that don't exist are visible, so looking them up afterwards reports them as not found.
+pub async fn ensure_profile_visible(
+    user_id: i64,
+    viewer_id: Option<i64>,
+    db: &PgPool,
+) -> Result<(), UserError> {
+    let visible = sqlx::query_scalar!(
+        r#"SELECT profile_visible_to($1, $2) as "visible!""#,
+        user_id,
+        viewer_id
+    )
+    .fetch_one(db)
+    .await?;
+
+    if visible {
+        Ok(())
+    } else {
+        Err(UserError::ProfileHidden(user_id))
+    }
+}
+
+/// Stores the privacy settings of a user.
+///
+/// Disallowing influence listing only stops new influences from being added. Existing ones stay
+/// until the user hides them through influence acknowledgement.
+pub async fn update_privacy_settings(
+    user_id: i64,
+    settings: PrivacySettings,
+    db: &PgPool,
+) -> Result<(), UserError> {
+    let update_result = sqlx::query!(
+        "UPDATE user_profiles SET (hide_profile, hide_influences, allow_influence_listing, \
+         modified_at) = ($1, $2, $3, DEFAULT) WHERE user_id = $4 RETURNING user_id",
+        settings.hide_profile,
+        settings.hide_influences,
+        settings.allow_influence_listing,
+        user_id,
+    )
+    .fetch_one(db)
+    .await;
+
+    match update_result {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::RowNotFound) => Err(UserError::UserNotFound(user_id)),
+        Err(db_err) => Err(UserError::from(db_err)),
+    }
+}
+
+/// Replaces the external links of a user. Links are expected to be validated beforehand.
+pub async fn update_user_social_links(
+    social_links: &[SocialLink],
+    user_id: i64,
+    db: &PgPool,
+) -> Result<(), UserError> {
+    let update_result = sqlx::query!(
+        "UPDATE user_profiles SET (social_links, modified_at) = ($1, DEFAULT) WHERE user_id = $2 \
+         RETURNING user_id",
+        Json(social_links) as _,
+        user_id,
+    )
+    .fetch_one(db)
+    .await;
+
+    match update_result {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::RowNotFound) => Err(UserError::UserNotFound(user_id)),
+        Err(db_err) => Err(UserError::from(db_err)),
+    }
+}
+
 pub async fn update_user_bio(
     user_bio: Option<&str>,
     user_id: i64,
@@ -375,19 +923,57 @@ pub async fn update_user_bio(
     }
 }
 
-// Currently, we don't need this
-//
-// pub async fn delete_user(user_id: i64, db: &PgPool) -> Result<(), UserError> {
-//     let delete_result = sqlx::query!("DELETE FROM users WHERE id = $1 RETURNING id", user_id)
-//         .fetch_one(db)
-//         .await;
+/// Deletes a user and everything stored about them in a single transaction.
+///
+/// Influences from and to the user are removed along with their tags and beatmaps, including the
+/// deleted ones that could still be restored. Influence history rows the user appears in are
+/// removed as well.
+pub async fn delete_user(user_id: i64, db: &PgPool) -> Result<(), UserError> {
+    let mut transaction = db.begin().await?;
 
-//     match delete_result {
-//         Ok(_) => Ok(()),
-//         Err(sqlx::Error::RowNotFound) => Err(UserError::UserNotFound(user_id)),
-//         Err(db_err) => Err(UserError::from(db_err)),
-//     }
-// }
+    let user_exists = sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+        .fetch_optional(&mut transaction)
+        .await?
+        .is_some();
+
+    if !user_exists {
+        return Err(UserError::UserNotFound(user_id));
+    }
+
+    sqlx::query!(
+        "DELETE FROM influences WHERE from_id = $1 OR to_id = $1",
+        user_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM influence_history WHERE from_id = $1 OR to_id = $1 OR actor_id = $1",
+        user_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!("DELETE FROM user_profiles WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await?;
+
+    sqlx::query!("DELETE FROM users_osu_data WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await?;
+
+    sqlx::query!("DELETE FROM user_osu_maps WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await?;
+
+    // Name history is removed through its foreign key
+    sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
+        .execute(&mut transaction)
+        .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
 
 #[derive(Debug, Error)]
 pub enum UserError {
@@ -395,6 +981,21 @@ pub enum UserError {
     UserNotFound(i64),
     #[error("User with id `{0}` already exists.")]
     UserAlreadyExists(i64),
+    #[error("No user is or was named `{0}`.")]
+    UserNameNotFound(String),
+    #[error("Profile of user `{0}` is only visible to logged in users.")]
+    ProfileHidden(i64),
+    #[error("Map with id `{0}` does not belong to any of the user's beatmapsets.")]
+    FeaturedMapNotOwned(i64),
+    #[error("Map with id `{0}` is featured more than once.")]
+    DuplicateFeaturedMap(i64),
+    #[error(
+        "A user can have at most {} featured maps, got {0}.",
+        FEATURED_MAPS_LIMIT
+    )]
+    TooManyFeaturedMaps(usize),
+    #[error("Given maps do not match the current featured maps.")]
+    FeaturedMapsMismatch,
     #[error("Internal database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
     #[error("Failed to serialize Json: {0}")]
@@ -406,6 +1007,12 @@ impl AppErrorExt for UserError {
         match self {
             UserError::UserNotFound(_) => self.to_string(),
             UserError::UserAlreadyExists(_) => self.to_string(),
+            UserError::UserNameNotFound(_) => self.to_string(),
+            UserError::ProfileHidden(_) => self.to_string(),
+            UserError::FeaturedMapNotOwned(_) => self.to_string(),
+            UserError::DuplicateFeaturedMap(_) => self.to_string(),
+            UserError::TooManyFeaturedMaps(_) => self.to_string(),
+            UserError::FeaturedMapsMismatch => self.to_string(),
             UserError::DatabaseError(_) => INTERNAL_DB_ERROR_MESSAGE.to_string(),
             UserError::SerdeError(_) => INTERNAL_DB_ERROR_MESSAGE.to_string(),
         }
@@ -415,6 +1022,12 @@ impl AppErrorExt for UserError {
         match self {
             UserError::UserNotFound(_) => ErrorType::DataNotFound,
             UserError::UserAlreadyExists(_) => ErrorType::DuplicateEntry,
+            UserError::UserNameNotFound(_) => ErrorType::DataNotFound,
+            UserError::ProfileHidden(_) => ErrorType::AuthorizatonError,
+            UserError::FeaturedMapNotOwned(_) => ErrorType::BadRequestData,
+            UserError::DuplicateFeaturedMap(_) => ErrorType::BadRequestData,
+            UserError::TooManyFeaturedMaps(_) => ErrorType::BadRequestData,
+            UserError::FeaturedMapsMismatch => ErrorType::BadRequestData,
             UserError::DatabaseError(_) => ErrorType::DatabaseError,
             UserError::SerdeError(_) => ErrorType::DeserializeError,
         }
@@ -424,6 +1037,12 @@ impl AppErrorExt for UserError {
         match self {
             UserError::UserNotFound(user_id) => warn!(user_id, "{}", self.to_string()),
             UserError::UserAlreadyExists(user_id) => warn!(user_id, "{}", self.to_string()),
+            UserError::UserNameNotFound(_) => warn!("{}", self.to_string()),
+            UserError::ProfileHidden(user_id) => warn!(user_id, "{}", self.to_string()),
+            UserError::FeaturedMapNotOwned(_) => warn!("{}", self.to_string()),
+            UserError::DuplicateFeaturedMap(_) => warn!("{}", self.to_string()),
+            UserError::TooManyFeaturedMaps(_) => warn!("{}", self.to_string()),
+            UserError::FeaturedMapsMismatch => warn!("{}", self.to_string()),
             UserError::DatabaseError(_) => error!("{}", self.to_string()),
             UserError::SerdeError(_) => error!("{}", self.to_string()),
         }
@@ -435,6 +1054,12 @@ impl UserError {
         match self {
             UserError::UserNotFound(_) => Level::WARN,
             UserError::UserAlreadyExists(_) => Level::WARN,
+            UserError::UserNameNotFound(_) => Level::WARN,
+            UserError::ProfileHidden(_) => Level::WARN,
+            UserError::FeaturedMapNotOwned(_) => Level::WARN,
+            UserError::DuplicateFeaturedMap(_) => Level::WARN,
+            UserError::TooManyFeaturedMaps(_) => Level::WARN,
+            UserError::FeaturedMapsMismatch => Level::WARN,
             UserError::DatabaseError(_) => Level::ERROR,
             UserError::SerdeError(_) => Level::ERROR,
         }
@@ -448,9 +1073,16 @@ impl From<UserError> for Level {
 
 #[cfg(all(test, feature = "db-tests"))]
 mod tests {
+    use mi_osu_api::{
+        Beatmap, BeatmapType, BeatmapsetNames, BeatmapsetStats, Country, Cover, Covers,
+    };
     use sqlx::PgPool;
 
     use super::*;
+    use crate::{
+        get_all_influences_by_to_id, get_influence_history, insert_influence, Influence,
+        InfluenceDirection,
+    };
 
     const NOT_FOUND_ERROR_TEXT: &str = "Query against absent users should return NotFound error.";
 
@@ -521,25 +1153,404 @@ mod tests {
         let db_user = get_user(user.id, &db).await.unwrap();
         assert_eq!(user.profile_picture, db_user.profile_picture);
         assert_eq!(user.user_name, db_user.user_name);
+
+        // Test social links update
+        let full_user = get_full_user(user.id, &db).await.unwrap();
+        assert!(full_user.social_links.is_empty());
+
+        let social_links = vec![
+            SocialLink {
+                platform: SocialPlatform::Twitch,
+                url: "https://www.twitch.tv/boraarslan".to_string(),
+            },
+            SocialLink {
+                platform: SocialPlatform::Website,
+                url: "https://boraarslan.dev".to_string(),
+            },
+        ];
+        update_user_social_links(&social_links, user.id, &db)
+            .await
+            .unwrap();
+        let full_user = get_full_user(user.id, &db).await.unwrap();
+        assert_eq!(full_user.social_links.0, social_links);
+    }
+
+    #[sqlx::test]
+    async fn test_search_users(db: PgPool) {
+        for (user_id, user_name) in [
+            (1, "boraarslan"),
+            (2, "Bora_"),
+            (3, "fursum"),
+            (4, "Boraxx"),
+        ] {
+            init_user(
+                User::new(user_id, user_name.to_string(), "picture.jpg".to_string()),
+                &db,
+            )
+            .await
+            .unwrap();
+        }
+
+        let users = search_users("bora", 10, 0, &db).await.unwrap();
+        let mut ids: Vec<i64> = users.iter().map(|user| user.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 2, 4]);
+
+        let users = search_users("bora", 2, 2, &db).await.unwrap();
+        assert_eq!(users.len(), 1);
+
+        // Underscore should not match any character
+        let users = search_users("a_", 10, 0, &db).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].id, 2);
+    }
+
+    fn mapset_for_test(mapset_id: i64, map_ids: &[i64]) -> Beatmapset {
+        Beatmapset {
+            id: mapset_id,
+            status: BeatmapType::Ranked,
+            creator: "boraarslan".to_string(),
+            beatmaps: map_ids
+                .iter()
+                .map(|&map_id| Beatmap {
+                    difficulty_rating: 5.0,
+                    id: map_id,
+                    beatmapset_id: mapset_id,
+                    url: format!("https://osu.ppy.sh/beatmaps/{map_id}"),
+                    name: "Insane".to_string(),
+                })
+                .collect(),
+            covers: Covers {
+                cover: "cover.jpg".to_string(),
+                card: "card.jpg".to_string(),
+                list: "list.jpg".to_string(),
+                slimcover: "slimcover.jpg".to_string(),
+            },
+            names: BeatmapsetNames {
+                artist: "Artist".to_string(),
+                artist_unicode: "Artist".to_string(),
+                title: "Title".to_string(),
+                title_unicode: "Title".to_string(),
+            },
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_featured_maps(db: PgPool) {
+        let user = user_for_test(1);
+        init_user(user.clone(), &db).await.unwrap();
+        upsert_user_mapsets(
+            user.id,
+            vec![
+                mapset_for_test(10, &[100, 101]),
+                mapset_for_test(20, &[200]),
+            ],
+            &db,
+        )
+        .await
+        .unwrap();
+
+        let featured_maps = set_user_featured_maps(user.id, &[200, 101], &db)
+            .await
+            .unwrap();
+        assert_eq!(featured_maps.maps[0].beatmapset.id, 20);
+        assert_eq!(featured_maps.maps[1].beatmapset.id, 10);
+
+        let err = set_user_featured_maps(user.id, &[300], &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UserError::FeaturedMapNotOwned(300)));
+
+        let err = set_user_featured_maps(user.id, &[100, 100], &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UserError::DuplicateFeaturedMap(100)));
+
+        let featured_maps = reorder_user_featured_maps(user.id, &[101, 200], &db)
+            .await
+            .unwrap();
+        assert_eq!(featured_maps.maps[0].featured_map_id, 101);
+        assert_eq!(featured_maps.maps[1].featured_map_id, 200);
+
+        let err = reorder_user_featured_maps(user.id, &[101, 100], &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UserError::FeaturedMapsMismatch));
+
+        let full_user = get_full_user(user.id, &db).await.unwrap();
+        assert_eq!(full_user.featured_maps.unwrap().maps.len(), 2);
+
+        clear_user_featured_maps(user.id, &db).await.unwrap();
+        let full_user = get_full_user(user.id, &db).await.unwrap();
+        assert!(full_user.featured_maps.is_none());
     }
 
-    // #[sqlx::test]
-    // async fn test_delete_user(db: PgPool) {
-    //     let user = user_for_test(1);
-    //     insert_user(user.clone(), &db).await.unwrap();
-    //     let db_user = get_user(user.id, &db).await.unwrap();
-    //     assert_eq!(user, db_user);
+    #[sqlx::test]
+    async fn test_stale_mapsets(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+
+        assert!(get_user_mapsets_modified_at(1, &db)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            get_users_with_stale_mapsets(10, &db).await.unwrap(),
+            vec![1, 2]
+        );
 
-    //     delete_user(user.id, &db).await.unwrap();
-    //     let err = get_user(user.id, &db).await.unwrap_err();
+        upsert_user_mapsets(1, vec![mapset_for_test(10, &[100])], &db)
+            .await
+            .unwrap();
 
-    //     match err {
-    //         UserError::UserNotFound(db_user_id) => {
-    //             assert_eq!(user.id, db_user_id)
-    //         }
-    //         _ => panic!("{}", NOT_FOUND_ERROR_TEXT),
-    //     }
-    // }
+        assert!(get_user_mapsets_modified_at(1, &db)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            get_users_with_stale_mapsets(10, &db).await.unwrap(),
+            vec![2]
+        );
+        assert_eq!(get_user_mapsets(1, &db).await.unwrap()[0].id, 10);
+    }
+
+    fn osu_user_for_test(user_id: i64, user_name: &str) -> mi_osu_api::User {
+        mi_osu_api::User {
+            avatar_url: "random.imageservice.com/boraarslan.jpg".to_string(),
+            id: user_id,
+            playmode: "osu".to_string(),
+            title: None,
+            username: user_name.to_string(),
+            country: Country {
+                code: "TR".to_string(),
+                name: "Turkey".to_string(),
+            },
+            cover: Cover {
+                custom_url: None,
+                url: Some("cover.jpg".to_string()),
+            },
+            groups: Vec::new(),
+            followers: 10,
+            stats: BeatmapsetStats {
+                ranked: 1,
+                loved: 0,
+                nominated: 0,
+                pending: 0,
+                graveyard: 2,
+                guest: 0,
+            },
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_privacy_settings(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+
+        let settings = get_privacy_settings(1, &db).await.unwrap();
+        assert_eq!(settings, PrivacySettings::default());
+
+        let new_settings = PrivacySettings {
+            hide_profile: true,
+            hide_influences: true,
+            allow_influence_listing: false,
+        };
+        update_privacy_settings(1, new_settings, &db).await.unwrap();
+        assert_eq!(get_privacy_settings(1, &db).await.unwrap(), new_settings);
+
+        let err = update_privacy_settings(-100, new_settings, &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UserError::UserNotFound(-100)));
+    }
+
+    #[sqlx::test]
+    async fn test_ensure_profile_visible(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+
+        ensure_profile_visible(1, None, &db).await.unwrap();
+
+        let settings = PrivacySettings {
+            hide_profile: true,
+            ..Default::default()
+        };
+        update_privacy_settings(1, settings, &db).await.unwrap();
+
+        let err = ensure_profile_visible(1, None, &db).await.unwrap_err();
+        assert!(matches!(err, UserError::ProfileHidden(1)));
+        ensure_profile_visible(1, Some(2), &db).await.unwrap();
+
+        // Missing users are left to the lookups
+        ensure_profile_visible(-100, None, &db).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_stale_osu_data(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+
+        let an_hour_ago = Utc::now() - chrono::Duration::hours(1);
+        assert!(get_users_with_stale_osu_data(an_hour_ago, 10, &db)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let in_a_minute = Utc::now() + chrono::Duration::minutes(1);
+        assert_eq!(
+            get_users_with_stale_osu_data(in_a_minute, 10, &db)
+                .await
+                .unwrap(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            get_users_with_stale_osu_data(in_a_minute, 1, &db)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        update_user_osu_data(osu_user_for_test(1, "boraarslan"), &db)
+            .await
+            .unwrap();
+        let full_user = get_full_user(1, &db).await.unwrap();
+        assert!(!full_user.is_outdated(&MaxAge(chrono::Duration::hours(3))));
+        assert!(full_user.is_outdated(&MaxAge(chrono::Duration::zero())));
+
+        // Freshly refreshed users come last
+        assert_eq!(
+            get_users_with_stale_osu_data(Utc::now() + chrono::Duration::minutes(1), 10, &db)
+                .await
+                .unwrap(),
+            vec![2, 1]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_failed_osu_data_refresh(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+
+        for (user_id, hours) in [(1, 2), (2, 1)] {
+            sqlx::query!(
+                "UPDATE users_osu_data SET modified_at = $1 WHERE user_id = $2",
+                Utc::now() - chrono::Duration::hours(hours),
+                user_id
+            )
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+
+        // User 1 is the most outdated, but it can't be refreshed
+        record_osu_data_refresh_attempt(1, &db).await.unwrap();
+        let half_an_hour_ago = Utc::now() - chrono::Duration::minutes(30);
+        assert_eq!(
+            get_users_with_stale_osu_data(half_an_hour_ago, 10, &db)
+                .await
+                .unwrap(),
+            vec![2]
+        );
+
+        // Once the attempt is stale as well, the user is retried after the others
+        let in_a_minute = Utc::now() + chrono::Duration::minutes(1);
+        assert_eq!(
+            get_users_with_stale_osu_data(in_a_minute, 10, &db)
+                .await
+                .unwrap(),
+            vec![2, 1]
+        );
+
+        let err = record_osu_data_refresh_attempt(-100, &db)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UserError::UserNotFound(-100)));
+    }
+
+    #[sqlx::test]
+    async fn test_user_name_history(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+
+        // Refreshing with the same name should not record anything
+        update_user_osu_data(osu_user_for_test(1, "boraarslan"), &db)
+            .await
+            .unwrap();
+        assert!(get_user_name_history(1, &db).await.unwrap().is_empty());
+
+        update_user_osu_data(osu_user_for_test(1, "Bora"), &db)
+            .await
+            .unwrap();
+        update_user_osu_data(osu_user_for_test(1, "fursum"), &db)
+            .await
+            .unwrap();
+
+        let db_user = get_user(1, &db).await.unwrap();
+        assert_eq!(db_user.user_name, "fursum");
+
+        let history = get_user_name_history(1, &db).await.unwrap();
+        let names: Vec<&str> = history.iter().map(|h| h.user_name.as_str()).collect();
+        assert_eq!(names, vec!["Bora", "boraarslan"]);
+
+        assert_eq!(get_user_by_name("FURSUM", &db).await.unwrap().id, 1);
+        assert_eq!(get_user_by_name("boraarslan", &db).await.unwrap().id, 1);
+
+        let err = get_user_by_name("nobody", &db).await.unwrap_err();
+        assert!(matches!(err, UserError::UserNameNotFound(_)));
+
+        let full_user = get_full_user(1, &db).await.unwrap();
+        assert_eq!(full_user.country_code, Some("TR".to_string()));
+        assert_eq!(full_user.cover_url, Some("cover.jpg".to_string()));
+        assert_eq!(full_user.followers, 10);
+    }
+
+    #[sqlx::test]
+    async fn test_delete_user(db: PgPool) {
+        let user = user_for_test(1);
+        init_user(user.clone(), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+        init_user(user_for_test(3), &db).await.unwrap();
+        let db_user = get_user(user.id, &db).await.unwrap();
+        assert_eq!(user.id, db_user.id);
+
+        insert_influence(Influence::new(1, 2, 5, None), &db)
+            .await
+            .unwrap();
+        insert_influence(Influence::new(3, 1, 5, None), &db)
+            .await
+            .unwrap();
+        insert_influence(Influence::new(3, 2, 5, None), &db)
+            .await
+            .unwrap();
+        upsert_user_mapsets(user.id, vec![mapset_for_test(10, &[100])], &db)
+            .await
+            .unwrap();
+
+        delete_user(user.id, &db).await.unwrap();
+        let err = get_user(user.id, &db).await.unwrap_err();
+
+        match err {
+            UserError::UserNotFound(db_user_id) => {
+                assert_eq!(user.id, db_user_id)
+            }
+            _ => panic!("{}", NOT_FOUND_ERROR_TEXT),
+        }
+
+        // Influences of other users are left untouched
+        let influences = get_all_influences_by_to_id(2, &db).await.unwrap();
+        assert_eq!(influences.len(), 1);
+        assert_eq!(influences[0].from_id, 3);
+
+        let history = get_influence_history(2, InfluenceDirection::Influencers, None, 10, 0, &db)
+            .await
+            .unwrap();
+        assert!(history.iter().all(|entry| entry.from_id != user.id));
+
+        // User can sign up again afterwards
+        init_user(user.clone(), &db).await.unwrap();
+        assert!(get_user_mapsets(user.id, &db).await.unwrap().is_empty());
+
+        let err = delete_user(-100, &db).await.unwrap_err();
+        assert!(matches!(err, UserError::UserNotFound(-100)));
+    }
 
     #[sqlx::test]
     async fn test_non_existent(db: PgPool) {
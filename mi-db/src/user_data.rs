@@ -0,0 +1,143 @@
+use chrono::Utc;
+use mi_osu_api::Beatmapset;
+use serde::Serialize;
+use sqlx::types::Json;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::{
+    get_full_user, get_user_mapsets, get_user_name_history, FullUser, Influence,
+    InfluenceAcknowledgement, InfluenceAction, InfluenceBeatmap, InfluenceHistory, UserError,
+    UserNameHistory,
+};
+
+/// Everything stored about a user. Returned to users that request a copy of their own data.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserDataExport {
+    pub user: FullUser,
+    /// Former names of the user
+    pub name_history: Vec<UserNameHistory>,
+    /// Beatmapsets of the user as they were on the last sync
+    pub mapsets: Vec<Beatmapset>,
+    /// Influence list of the user, including the deleted influences that can still be restored
+    pub influences: Vec<Influence>,
+    /// Influences where other users listed the user as their influencer, including hidden ones
+    pub mentions: Vec<Influence>,
+    /// Every recorded change to an influence the user is a part of, newest first
+    pub influence_history: Vec<InfluenceHistory>,
+    /// Date of the export
+    pub exported_at: chrono::DateTime<Utc>,
+}
+
+/// Collects everything stored about a user into a [`UserDataExport`].
+pub async fn get_user_data_export(user_id: i64, db: &PgPool) -> Result<UserDataExport, UserError> {
+    let user = get_full_user(user_id, db).await?;
+    let name_history = get_user_name_history(user_id, db).await?;
+    let mapsets = get_user_mapsets(user_id, db).await?;
+
+    let influences = sqlx::query_as!(
+        Influence,
+        r#"
+        SELECT
+            from_id, to_id, influence_level, info, position,
+            acknowledgement as "acknowledgement: InfluenceAcknowledgement",
+            influence_tag_names(from_id, to_id) as "tags!",
+            influence_beatmap_list(from_id, to_id) as "beatmaps!: Json<Vec<InfluenceBeatmap>>",
+            created_at, modified_at, deleted_at
+        FROM influences
+        WHERE to_id = $1
+        ORDER BY position, from_id"#,
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mentions = sqlx::query_as!(
+        Influence,
+        r#"
+        SELECT
+            from_id, to_id, influence_level, info, position,
+            acknowledgement as "acknowledgement: InfluenceAcknowledgement",
+            influence_tag_names(from_id, to_id) as "tags!",
+            influence_beatmap_list(from_id, to_id) as "beatmaps!: Json<Vec<InfluenceBeatmap>>",
+            created_at, modified_at, deleted_at
+        FROM influences
+        WHERE from_id = $1 AND deleted_at IS NULL
+        ORDER BY to_id"#,
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let influence_history = sqlx::query_as!(
+        InfluenceHistory,
+        r#"
+        SELECT
+            id, from_id, to_id, action as "action: InfluenceAction", actor_id,
+            old_influence_level, new_influence_level, old_info, new_info, old_tags, new_tags,
+            created_at
+        FROM influence_history
+        WHERE from_id = $1 OR to_id = $1 OR actor_id = $1
+        ORDER BY created_at DESC, id DESC"#,
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(UserDataExport {
+        user,
+        name_history,
+        mapsets,
+        influences,
+        mentions,
+        influence_history,
+        exported_at: Utc::now(),
+    })
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::influence::{delete_influence, insert_influence};
+    use crate::user::{init_user, User};
+
+    fn user_for_test(user_id: i64) -> User {
+        User::new(
+            user_id,
+            "boraarslan".to_string(),
+            "random.imageservice.com/boraarslan.jpg".to_string(),
+        )
+    }
+
+    #[sqlx::test]
+    async fn test_user_data_export(db: PgPool) {
+        init_user(user_for_test(1), &db).await.unwrap();
+        init_user(user_for_test(2), &db).await.unwrap();
+        init_user(user_for_test(3), &db).await.unwrap();
+
+        insert_influence(Influence::new(2, 1, 5, None), &db)
+            .await
+            .unwrap();
+        insert_influence(Influence::new(3, 1, 5, None), &db)
+            .await
+            .unwrap();
+        insert_influence(Influence::new(1, 3, 5, None), &db)
+            .await
+            .unwrap();
+        delete_influence(3, 1, 1, &db).await.unwrap();
+
+        let export = get_user_data_export(1, &db).await.unwrap();
+        assert_eq!(export.user.id, 1);
+        assert_eq!(export.influences.len(), 2);
+        assert!(export.influences[1].deleted_at.is_some());
+        assert_eq!(export.mentions.len(), 1);
+        assert_eq!(export.mentions[0].to_id, 3);
+        // Two creations and a deletion on the user's list, one creation on user 3's list
+        assert_eq!(export.influence_history.len(), 4);
+
+        let err = get_user_data_export(-100, &db).await.unwrap_err();
+        assert!(matches!(err, UserError::UserNotFound(-100)));
+    }
+}
@@ -6,7 +6,8 @@ use tracing::error;
 
 use crate::RedisPool;
 
-pub async fn lock_user(user_id: i64, db: &RedisPool) -> Result<(), LockError> {
+/// Locks the user for 30 seconds. Returns `false` if the user is already locked.
+pub async fn lock_user(user_id: i64, db: &RedisPool) -> Result<bool, LockError> {
     let mut conn = db.get().await?;
     let mut cmd = redis::Cmd::new();
     let key = format!("user:lock:{}", user_id);
@@ -18,9 +19,10 @@ pub async fn lock_user(user_id: i64, db: &RedisPool) -> Result<(), LockError> {
         .arg("PX")
         .arg(30000);
 
-    cmd.query_async(&mut *conn).await?;
+    // SET with NX replies with nil if the key already exists
+    let reply: Option<String> = cmd.query_async(&mut *conn).await?;
 
-    Ok(())
+    Ok(reply.is_some())
 }
 
 pub async fn is_user_locked(user_id: i64, db: &RedisPool) -> Result<bool, LockError> {
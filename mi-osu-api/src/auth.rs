@@ -74,6 +74,18 @@ impl AuthRequest {
             refresh_token: Some(refresh_token),
         }
     }
+
+    fn client_credentials() -> AuthRequest {
+        AuthRequest {
+            client_id: &OSU_CLIENT_ID,
+            client_secret: &OSU_CLIENT_SECRET,
+            redirect_uri: &OSU_REDIRECT_URI,
+            grant_type: "client_credentials",
+            scope: "public",
+            code: None,
+            refresh_token: None,
+        }
+    }
 }
 
 /// Auth response body. Returned after authentication requests such as [`access_token`] and
@@ -90,6 +102,19 @@ pub struct AuthResponseBody {
     pub refresh_token: String,
 }
 
+/// Client credentials response body. Returned after [`client_credentials_token`] requests.
+///
+/// Unlike [`AuthResponseBody`], it has no refresh token. A new token is requested once it expires.
+#[derive(Deserialize, Debug)]
+pub struct ClientCredentialsResponseBody {
+    /// Bearer token
+    pub token_type: String,
+    /// Token validity duration in seconds
+    pub expires_in: u32,
+    /// An access token to authorize requests on endpoints
+    pub access_token: String,
+}
+
 fn check_scope(access_token: &str) -> Result<(), OsuApiError> {
     let parsed_token: Token<Header, Scopes, _> = Token::parse_unverified(access_token)?;
     if !parsed_token.claims().scopes.contains(&"public".to_string()) {
@@ -135,3 +160,48 @@ pub async fn access_token(client: &Client, code: String) -> Result<AuthResponseB
     check_scope(&requested_token.access_token)?;
     Ok(requested_token)
 }
+
+/// Client credentials request method. Returns a [`ClientCredentialsResponseBody`] with a token that
+/// belongs to the application itself rather than a user.
+///
+/// The token only has the "public" scope. It is used for requests that are not made on behalf of a
+/// user, such as background refreshes. For more information, check the [client credentials grant]
+/// section on osu! API documentation.
+///
+/// [client credentials grant]: <https://osu.ppy.sh/docs/index.html#client-credentials-grant>
+pub async fn client_credentials_token(
+    client: &Client,
+) -> Result<ClientCredentialsResponseBody, OsuApiError> {
+    let response_result = client
+        .post("https://osu.ppy.sh/oauth/token")
+        .form(&AuthRequest::client_credentials())
+        .send()
+        .await?;
+
+    response_result.try_deser_api_response().await
+}
+
+/// Revokes the given access token along with the refresh token issued with it.
+///
+/// For more information, check the [revoke current token] section on osu! API documentation.
+///
+/// [revoke current token]: <https://osu.ppy.sh/docs/index.html#revoke-current-token>
+pub async fn revoke_token(client: &Client, access_token: &str) -> Result<(), OsuApiError> {
+    let response = client
+        .delete("https://osu.ppy.sh/api/v2/oauth/tokens/current")
+        .bearer_auth(access_token)
+        .send()
+        .await?;
+
+    match response.error_for_status_ref() {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            let status = err.status().unwrap();
+            let body = response.text().await?;
+            Err(OsuApiError::HTTPError {
+                body,
+                error: status,
+            })
+        }
+    }
+}
@@ -93,6 +93,8 @@ pub struct Beatmap {
     pub difficulty_rating: f64,
     /// Unique ID of the beatmap. Different from [beatmapset ID](Beatmapset::id)
     pub id: i64,
+    /// ID of the [beatmapset](Beatmapset) this beatmap belongs to
+    pub beatmapset_id: i64,
     /// Url of the beatmap
     pub url: String,
     /// Difficulty name
@@ -119,13 +121,17 @@ pub struct Covers {
 /// Type of a beatmap.
 ///
 /// These are the variants of map types that are in users profile.
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-#[serde(rename_all(deserialize = "lowercase"))]
+/// Serialized in lowercase as well, so that beatmapsets stored by us can be deserialized again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
 pub enum BeatmapType {
     Graveyard,
     Loved,
     /// Includes Pending and WIP maps.
+    #[serde(alias = "wip")]
     Pending,
+    /// Includes Approved and Qualified maps, like the ranked section of osu! profiles.
+    #[serde(alias = "approved", alias = "qualified")]
     Ranked,
     Guest,
     Nominated,
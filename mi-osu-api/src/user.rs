@@ -13,6 +13,7 @@
 #![allow(dead_code)]
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use crate::{OsuApiError, ResponseWithBody};
 
@@ -103,7 +104,7 @@ pub struct Cover {
 /// [the official osu! API] for more information.
 ///
 /// [the official osu! API]: <https://osu.ppy.sh/docs/index.html#user>
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct UserGroup {
     /// Probationary users don't have small icons in their profiles
     pub is_probationary: bool,
@@ -119,7 +120,7 @@ pub struct UserGroup {
 
 #[derive(Debug, Deserialize)]
 pub struct SearchResultWrapper {
-    user: SearchResult,
+    pub user: SearchResult,
 }
 
 /// Wrapper for UserCompact. This struct also includes the number of possible users for this query.
@@ -144,7 +145,7 @@ pub struct SearchResult {
 /// [the official osu! API] for more information.
 ///
 /// [the official osu! API]: <https://osu.ppy.sh/docs/index.html#usercompact>
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct UserCompact {
     /// User's profile picture link
     pub avatar_url: String,
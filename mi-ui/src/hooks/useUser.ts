@@ -1,32 +1,30 @@
 import { useBaseUser } from "@services/user";
+import axios from "axios";
 import { useRouter } from "next/router";
 import { useEffect } from "react";
-import { useCookies } from "react-cookie";
 import { useSessionStore } from "src/states/user";
 
 export const useCurrentUser = () => {
   const router = useRouter();
   const { user, login, logout } = useSessionStore();
-  const [cookie, _, deleteCookie] = useCookies(["mi-session-token"]);
-  const { data, isLoading } = useBaseUser();
-
-  const sessionToken = cookie["mi-session-token"];
+  // Session cookie is not readable from here, the user is logged in if they can be fetched
+  const { data, isLoading, isError } = useBaseUser();
 
   useEffect(() => {
-    if (!user && sessionToken && data) login(data);
+    if (!user && data) login(data);
 
-    if (!sessionToken) {
+    if (isError) {
       logout();
       if (router.pathname !== "/") router.push("/");
     }
-  }, [sessionToken, user, login, logout, router, data]);
+  }, [isError, user, login, logout, router, data]);
 
   return {
     isLoading,
     user,
     logout: () => {
       logout();
-      deleteCookie("mi-session-token");
+      axios.post("/api/v1/session/logout");
     },
   };
 };
@@ -100,7 +100,6 @@ export const useFullUser = (userId?: number | string) => {
 };
 
 export type UserEditRequest = {
-  user_name?: string;
   profile_picture?: string;
   bio?: string;
 };
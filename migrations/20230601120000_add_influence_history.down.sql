@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS influence_history;
+DROP TYPE IF EXISTS influence_action;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+CREATE TYPE influence_action AS ENUM ('create', 'update', 'delete');
+
+-- History rows are not bound to `users` with foreign keys so they outlive the influences they
+-- describe.
+CREATE TABLE IF NOT EXISTS influence_history(
+    id BIGSERIAL PRIMARY KEY,
+    from_id BIGINT NOT NULL,
+    to_id BIGINT NOT NULL,
+    action influence_action NOT NULL,
+    actor_id BIGINT NOT NULL,
+    old_influence_level INT,
+    new_influence_level INT,
+    old_info TEXT,
+    new_info TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS influence_history_from_id_idx ON influence_history(from_id, created_at);
+CREATE INDEX IF NOT EXISTS influence_history_to_id_idx ON influence_history(to_id, created_at);
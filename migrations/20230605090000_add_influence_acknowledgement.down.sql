@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+ALTER TABLE influences DROP COLUMN IF EXISTS acknowledgement;
+DROP TYPE IF EXISTS influence_acknowledgement;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+CREATE TYPE influence_acknowledgement AS ENUM ('pending', 'acknowledged', 'hidden');
+
+ALTER TABLE influences ADD acknowledgement influence_acknowledgement NOT NULL DEFAULT 'pending';
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP FUNCTION IF EXISTS influence_tag_names(BIGINT, BIGINT);
+ALTER TABLE influence_history DROP COLUMN IF EXISTS old_tags, DROP COLUMN IF EXISTS new_tags;
+DROP TABLE IF EXISTS influence_tag_links;
+DROP TABLE IF EXISTS influence_tags;
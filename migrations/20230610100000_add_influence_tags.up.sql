@@ -0,0 +1,44 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS influence_tags(
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO influence_tags (name, description) VALUES
+    ('rhythm', 'Rhythm choices and what is mapped to the song'),
+    ('aesthetics', 'Visual flow, shapes and overall look of the map'),
+    ('patterning', 'Object placement and pattern design'),
+    ('structure', 'How sections of the song are structured and emphasized'),
+    ('hitsounding', 'Hitsound and sample choices'),
+    ('storyboarding', 'Storyboard and visual effects'),
+    ('difficulty spread', 'Difficulty progression across a beatmapset'),
+    ('modding', 'Feedback and modding style');
+
+CREATE TABLE IF NOT EXISTS influence_tag_links(
+    from_id BIGINT NOT NULL,
+    to_id BIGINT NOT NULL,
+    tag_id INT NOT NULL REFERENCES influence_tags(id),
+    CONSTRAINT pk_influence_tag_link PRIMARY KEY (from_id, to_id, tag_id),
+    CONSTRAINT fk_influence_tag_link_influence FOREIGN KEY (from_id, to_id)
+        REFERENCES influences(from_id, to_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS influence_tag_links_tag_id_idx ON influence_tag_links(tag_id);
+
+-- Tag changes are recorded on the history next to info changes. Both are null if the change
+-- didn't touch the tags.
+ALTER TABLE influence_history ADD old_tags TEXT[], ADD new_tags TEXT[];
+
+-- Tag names of an influence, in vocabulary order.
+CREATE OR REPLACE FUNCTION influence_tag_names(from_id BIGINT, to_id BIGINT)
+RETURNS TEXT[] AS $$
+    SELECT ARRAY(
+        SELECT t.name FROM influence_tag_links l
+        INNER JOIN influence_tags t ON t.id = l.tag_id
+        WHERE l.from_id = $1 AND l.to_id = $2
+        ORDER BY t.id
+    )
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP FUNCTION IF EXISTS influence_beatmap_list(BIGINT, BIGINT);
+DROP TABLE IF EXISTS influence_beatmaps;
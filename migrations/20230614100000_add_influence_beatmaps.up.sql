@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+-- Beatmap data is stored as it was when the beatmap got attached, so listings don't need to reach
+-- osu! API.
+CREATE TABLE IF NOT EXISTS influence_beatmaps(
+    from_id BIGINT NOT NULL,
+    to_id BIGINT NOT NULL,
+    position INT NOT NULL,
+    beatmap JSON NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT pk_influence_beatmap PRIMARY KEY (from_id, to_id, position),
+    CONSTRAINT fk_influence_beatmap_influence FOREIGN KEY (from_id, to_id)
+        REFERENCES influences(from_id, to_id) ON DELETE CASCADE
+);
+
+-- Beatmaps of an influence as a JSON array, in the order the user put them.
+CREATE OR REPLACE FUNCTION influence_beatmap_list(from_id BIGINT, to_id BIGINT)
+RETURNS JSON AS $$
+    SELECT COALESCE(json_agg(b.beatmap ORDER BY b.position), '[]') FROM influence_beatmaps b
+    WHERE b.from_id = $1 AND b.to_id = $2
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS idx_influences_to_id_position;
+ALTER TABLE influences DROP COLUMN IF EXISTS position;
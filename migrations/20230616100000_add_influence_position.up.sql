@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+ALTER TABLE influences ADD position INT NOT NULL DEFAULT 0;
+
+-- Keep the current order of existing influences, oldest first
+UPDATE influences SET position = ordered.position
+FROM (
+    SELECT from_id, to_id, (ROW_NUMBER() OVER (PARTITION BY to_id ORDER BY created_at, from_id) - 1)::INT AS position
+    FROM influences
+) ordered
+WHERE influences.from_id = ordered.from_id AND influences.to_id = ordered.to_id;
+
+CREATE INDEX IF NOT EXISTS idx_influences_to_id_position ON influences(to_id, position);
@@ -0,0 +1,14 @@
+-- Add down migration script here
+
+-- Soft deleted influences can't be kept without the column
+DELETE FROM influences WHERE deleted_at IS NOT NULL;
+
+DROP INDEX IF EXISTS idx_influences_deleted_at;
+ALTER TABLE influences DROP COLUMN IF EXISTS deleted_at;
+
+-- Enum values can't be dropped, so the type is recreated without 'restore'
+DELETE FROM influence_history WHERE action = 'restore';
+ALTER TYPE influence_action RENAME TO influence_action_old;
+CREATE TYPE influence_action AS ENUM ('create', 'update', 'delete');
+ALTER TABLE influence_history ALTER COLUMN action TYPE influence_action USING action::TEXT::influence_action;
+DROP TYPE influence_action_old;
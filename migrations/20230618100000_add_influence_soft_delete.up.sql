@@ -0,0 +1,7 @@
+-- Add up migration script here
+
+ALTER TABLE influences ADD deleted_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_influences_deleted_at ON influences(deleted_at) WHERE deleted_at IS NOT NULL;
+
+ALTER TYPE influence_action ADD VALUE IF NOT EXISTS 'restore';
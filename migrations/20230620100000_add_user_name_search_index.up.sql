@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Speeds up substring searches with ILIKE and similarity ordering on user names
+CREATE INDEX IF NOT EXISTS idx_users_user_name_trgm ON users USING GIN (user_name gin_trgm_ops);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+ALTER TABLE user_osu_maps DROP CONSTRAINT IF EXISTS pk_user_osu_maps;
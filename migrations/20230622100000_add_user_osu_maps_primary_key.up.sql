@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- Mapsets are upserted per user, which needs a unique user_id
+ALTER TABLE user_osu_maps ADD CONSTRAINT pk_user_osu_maps PRIMARY KEY (user_id);
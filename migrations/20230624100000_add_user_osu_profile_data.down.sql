@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+ALTER TABLE users_osu_data DROP COLUMN IF EXISTS country_code;
+ALTER TABLE users_osu_data DROP COLUMN IF EXISTS country_name;
+ALTER TABLE users_osu_data DROP COLUMN IF EXISTS groups;
+ALTER TABLE users_osu_data DROP COLUMN IF EXISTS title;
+ALTER TABLE users_osu_data DROP COLUMN IF EXISTS cover_url;
+ALTER TABLE users_osu_data DROP COLUMN IF EXISTS followers;
+ALTER TABLE users_osu_data DROP COLUMN IF EXISTS playmode;
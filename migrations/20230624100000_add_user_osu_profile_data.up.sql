@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+ALTER TABLE users_osu_data ADD country_code TEXT;
+ALTER TABLE users_osu_data ADD country_name TEXT;
+ALTER TABLE users_osu_data ADD groups JSON NOT NULL DEFAULT '[]';
+ALTER TABLE users_osu_data ADD title TEXT;
+ALTER TABLE users_osu_data ADD cover_url TEXT;
+ALTER TABLE users_osu_data ADD followers BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE users_osu_data ADD playmode TEXT;
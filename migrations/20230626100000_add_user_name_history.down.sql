@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS idx_users_lower_user_name;
+DROP TABLE IF EXISTS user_name_history;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS user_name_history(
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Name the user had before the change
+    user_name TEXT NOT NULL,
+    changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_name_history_user_id ON user_name_history(user_id);
+CREATE INDEX IF NOT EXISTS idx_user_name_history_lower_user_name ON user_name_history(LOWER(user_name));
+CREATE INDEX IF NOT EXISTS idx_users_lower_user_name ON users(LOWER(user_name));
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+ALTER TABLE users_osu_data DROP COLUMN IF EXISTS last_refresh_attempt_at;
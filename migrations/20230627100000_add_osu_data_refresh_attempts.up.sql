@@ -0,0 +1,3 @@
+-- Add up migration script here
+
+ALTER TABLE users_osu_data ADD last_refresh_attempt_at TIMESTAMP WITH TIME ZONE;
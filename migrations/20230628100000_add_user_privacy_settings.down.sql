@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP FUNCTION IF EXISTS influence_visible_to(BIGINT, BIGINT, BIGINT);
+DROP FUNCTION IF EXISTS profile_visible_to(BIGINT, BIGINT);
+
+ALTER TABLE user_profiles
+    DROP COLUMN IF EXISTS hide_profile,
+    DROP COLUMN IF EXISTS hide_influences,
+    DROP COLUMN IF EXISTS allow_influence_listing;
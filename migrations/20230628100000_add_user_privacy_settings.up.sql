@@ -0,0 +1,25 @@
+-- Add up migration script here
+ALTER TABLE user_profiles
+    ADD COLUMN hide_profile BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN hide_influences BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN allow_influence_listing BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Whether the profile of a user can be seen by the viewer. Viewer is null for requests that are
+-- not logged in, which can't see hidden profiles.
+CREATE OR REPLACE FUNCTION profile_visible_to(user_id BIGINT, viewer_id BIGINT)
+RETURNS BOOLEAN AS $$
+    SELECT $2 IS NOT NULL OR NOT EXISTS (
+        SELECT 1 FROM user_profiles p WHERE p.user_id = $1 AND p.hide_profile
+    )
+$$ LANGUAGE SQL STABLE;
+
+-- Whether an influence can be seen by the viewer. Both users have to be visible to the viewer, and
+-- a hidden influence list is only visible to its owner. Acknowledgements are checked separately.
+CREATE OR REPLACE FUNCTION influence_visible_to(from_id BIGINT, to_id BIGINT, viewer_id BIGINT)
+RETURNS BOOLEAN AS $$
+    SELECT $2 IS NOT DISTINCT FROM $3 OR (
+        profile_visible_to($1, $3)
+        AND profile_visible_to($2, $3)
+        AND NOT EXISTS (SELECT 1 FROM user_profiles p WHERE p.user_id = $2 AND p.hide_influences)
+    )
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE user_profiles DROP COLUMN IF EXISTS social_links;
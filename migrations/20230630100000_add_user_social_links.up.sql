@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE user_profiles ADD COLUMN social_links JSON NOT NULL DEFAULT '[]';